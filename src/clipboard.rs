@@ -1,3 +1,11 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    borrow::Cow,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use raylib::texture;
+
 #[cfg(not(target_arch = "wasm32"))]
 pub struct ImageData {
     pub bytes: Vec<u8>,
@@ -5,6 +13,16 @@ pub struct ImageData {
     pub height: usize,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+impl ImageData {
+    /// Tells images apart without keeping their pixels around.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.width, self.height, &self.bytes).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub struct Clipboard(arboard::Clipboard);
 
@@ -30,6 +48,22 @@ impl Clipboard {
             height: img.height,
         })
     }
+
+    pub fn set_image(&mut self, image: &texture::Image) -> Result<(), String> {
+        let bytes = image
+            .get_image_data()
+            .iter()
+            .flat_map(|c| [c.r, c.g, c.b, c.a])
+            .collect::<Vec<_>>();
+        let data = arboard::ImageData {
+            width: image.width as usize,
+            height: image.height as usize,
+            bytes: Cow::Owned(bytes),
+        };
+        self.0
+            .set_image(data)
+            .map_err(|e| format!("Couldn't copy the image ({e})"))
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -47,4 +81,8 @@ impl Clipboard {
     pub fn get_image(&mut self) -> Option<Self> {
         None
     }
+
+    pub fn set_image(&mut self, _image: &texture::Image) -> Result<(), String> {
+        Err("Copying images isn't supported".to_string())
+    }
 }
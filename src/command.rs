@@ -1,27 +1,97 @@
 use std::collections::VecDeque;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
+use std::time::{Duration, Instant};
 
-use widok::{CanvasLength, CanvasPoint};
+use widok::{CanvasLength, CanvasPoint, CanvasVector};
 
-use crate::graphics::{Contents, Eraser, Image, ImageId, Line};
+use crate::graphics::{
+    Arrow, Contents, Ellipse, Eraser, IdMark, Image, ImageId, Line, Rectangle, Snapshot, Text,
+};
+use crate::selection::Selection;
 
 #[derive(Debug)]
 pub struct CommandInvoker {
     undos: VecDeque<Box<dyn Command>>,
     redos: VecDeque<Box<dyn Command>>,
     buffer_size: usize,
+    // commands pushed within this time of each other are undone together
+    group_time: Duration,
+    last_push: Option<Instant>,
+    // when an eraser was last pushed, for merging it with the next one
+    last_eraser: Option<Instant>,
+    // whether anything changed since the canvas was last saved
+    dirty: bool,
+    // counts every change, to tell if anything happened since some point
+    changes: usize,
 }
 
 impl CommandInvoker {
-    pub fn new(buffer_size: usize) -> Self {
+    pub fn new(buffer_size: usize, group_time: Duration) -> Self {
         Self {
             undos: VecDeque::<Box<dyn Command>>::new(),
             redos: VecDeque::<Box<dyn Command>>::new(),
             buffer_size,
+            group_time,
+            last_push: None,
+            last_eraser: None,
+            dirty: false,
+            changes: 0,
         }
     }
 
+    /// Changes how many commands are kept and how close ones get grouped,
+    /// the oldest ones that don't fit anymore are dropped.
+    pub fn set_limits(&mut self, buffer_size: usize, group_time: Duration) {
+        self.buffer_size = buffer_size;
+        self.group_time = group_time;
+        while self.undos.len() > buffer_size {
+            self.undos.pop_front();
+        }
+        while self.redos.len() > buffer_size {
+            self.redos.pop_front();
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+    }
+
+    pub fn mark_unsaved(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn changes(&self) -> usize {
+        self.changes
+    }
+
     pub fn push<T: Command + 'static>(&mut self, command: T) {
+        self.dirty = true;
+        self.changes += 1;
+        // a new change makes the undone ones unreachable
+        self.redos.clear();
+        self.last_eraser = None;
+        let now = Instant::now();
+        let grouped = !self.group_time.is_zero()
+            && self
+                .last_push
+                .is_some_and(|t| now.duration_since(t) <= self.group_time);
+        self.last_push = Some(now);
+
+        if grouped && let Some(last) = self.undos.back_mut() {
+            if let Some(group) = last.as_group() {
+                group.commands.push(Box::new(command));
+            } else {
+                let last = self.undos.pop_back().unwrap();
+                self.undos
+                    .push_back(Box::new(CommandGroup::new(vec![last, Box::new(command)])));
+            }
+            return;
+        }
+
         while self.undos.len() >= self.buffer_size {
             self.undos.pop_front();
         }
@@ -29,30 +99,219 @@ impl CommandInvoker {
         self.undos.push_back(Box::new(command));
     }
 
-    pub fn undo(&mut self, contents: &mut Contents) {
-        if let Some(mut command) = self.undos.pop_back() {
-            command.undo(contents);
-            while self.redos.len() >= self.buffer_size {
-                self.redos.pop_front();
-            }
-            self.redos.push_back(command);
+    /// Pushes an eraser, adding it to the previous command if that one also
+    /// added erasers within `merge_time`, so erasing in several gestures
+    /// can be undone at once.
+    pub fn push_eraser(&mut self, eraser: Eraser, merge_time: Duration) {
+        let now = Instant::now();
+        let merged = !merge_time.is_zero()
+            && self
+                .last_eraser
+                .is_some_and(|t| now.duration_since(t) <= merge_time);
+
+        if merged && let Some(add) = self.undos.back_mut().and_then(|c| c.as_eraser()) {
+            add.erasers.push(eraser);
+            self.dirty = true;
+            self.changes += 1;
+            self.redos.clear();
+        } else {
+            self.push(AddEraser::new(eraser));
+        }
+        self.last_eraser = Some(now);
+    }
+
+    /// Commands that can be undone, oldest first.
+    pub fn undos(&self) -> impl DoubleEndedIterator<Item = &dyn Command> {
+        self.undos.iter().map(|c| c.as_ref())
+    }
+
+    /// Commands that can be redone, the next one first.
+    pub fn redos(&self) -> impl DoubleEndedIterator<Item = &dyn Command> {
+        self.redos.iter().rev().map(|c| c.as_ref())
+    }
+
+    /// Makes sure the next command doesn't get grouped with the previous one.
+    pub fn end_group(&mut self) {
+        self.last_push = None;
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undos.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redos.len()
+    }
+
+    /// Returns whether there was anything to undo.
+    pub fn undo(&mut self, contents: &mut Contents) -> bool {
+        self.end_group();
+        self.last_eraser = None;
+        let Some(mut command) = self.undos.pop_back() else {
+            return false;
+        };
+        self.dirty = true;
+        self.changes += 1;
+        command.undo(contents);
+        while self.redos.len() >= self.buffer_size {
+            self.redos.pop_front();
+        }
+        self.redos.push_back(command);
+        true
+    }
+
+    /// Returns whether there was anything to redo.
+    pub fn redo(&mut self, contents: &mut Contents) -> bool {
+        self.end_group();
+        self.last_eraser = None;
+        let Some(mut command) = self.redos.pop_back() else {
+            return false;
+        };
+        self.dirty = true;
+        self.changes += 1;
+        command.execute(contents);
+        while self.undos.len() >= self.buffer_size {
+            self.undos.pop_front();
         }
+        self.undos.push_back(command);
+        true
+    }
+}
+
+/// The canvas as it was loaded and what got added to it since, kept apart
+/// from the history so playing it back doesn't touch the commands.
+#[derive(Debug)]
+pub struct Timeline {
+    start: Snapshot,
+    strokes: Vec<Stroke>,
+    // drawables with later ids haven't been recorded yet
+    mark: IdMark,
+    changes: usize,
+}
+
+/// What a single change added to the canvas.
+#[derive(Debug, Clone)]
+pub struct Stroke {
+    pub time: Instant,
+    pub added: Snapshot,
+}
+
+impl Timeline {
+    /// Starts recording on top of `contents`, `changes` is the invoker's count.
+    pub fn new(contents: &Contents, changes: usize) -> Self {
+        Self {
+            start: contents.snapshot(),
+            strokes: vec![],
+            mark: contents.id_mark(),
+            changes,
+        }
+    }
+
+    /// Records what got added when there was a change since the last call.
+    pub fn record(&mut self, contents: &Contents, changes: usize) {
+        if changes == self.changes {
+            return;
+        }
+        self.changes = changes;
+        let added = contents.added_since(self.mark);
+        self.mark = contents.id_mark();
+        if !added.is_empty() {
+            self.strokes.push(Stroke {
+                time: Instant::now(),
+                added,
+            });
+        }
+    }
+
+    /// Copies of the start and the strokes with what was removed or undone
+    /// since left out, so playback ends on the current drawing.
+    pub fn replay(&self, contents: &Contents) -> (Snapshot, Vec<Stroke>) {
+        let mut start = self.start.clone();
+        start.retain_present(contents);
+        let strokes = self
+            .strokes
+            .iter()
+            .filter_map(|s| {
+                let mut stroke = s.clone();
+                stroke.added.retain_present(contents);
+                (!stroke.added.is_empty()).then_some(stroke)
+            })
+            .collect();
+        (start, strokes)
+    }
+}
+
+/// The `Debug` representation of `command` cut to `max_len` characters.
+pub fn describe(command: &dyn Command, max_len: usize) -> String {
+    struct Truncated {
+        text: String,
+        left: usize,
     }
 
-    pub fn redo(&mut self, contents: &mut Contents) {
-        if let Some(mut command) = self.redos.pop_back() {
-            command.execute(contents);
-            while self.undos.len() >= self.buffer_size {
-                self.undos.pop_front();
+    impl fmt::Write for Truncated {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            for c in s.chars() {
+                if self.left == 0 {
+                    // stops formatting the rest, lines can have a lot of points
+                    return Err(fmt::Error);
+                }
+                self.text.push(c);
+                self.left -= 1;
             }
-            self.undos.push_back(command);
+            Ok(())
         }
     }
+
+    let mut out = Truncated {
+        text: String::new(),
+        left: max_len,
+    };
+    if fmt::write(&mut out, format_args!("{command:?}")).is_err() {
+        out.text.push_str("...");
+    }
+    out.text
 }
 
 pub trait Command: Debug {
     fn execute(&mut self, contents: &mut Contents);
     fn undo(&mut self, contents: &mut Contents);
+
+    fn as_group(&mut self) -> Option<&mut CommandGroup> {
+        None
+    }
+
+    fn as_eraser(&mut self) -> Option<&mut AddEraser> {
+        None
+    }
+}
+
+/// Several commands undone and redone as one.
+#[derive(Debug)]
+pub struct CommandGroup {
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl CommandGroup {
+    pub fn new(commands: Vec<Box<dyn Command>>) -> Self {
+        Self { commands }
+    }
+}
+
+impl Command for CommandGroup {
+    fn execute(&mut self, contents: &mut Contents) {
+        self.commands.iter_mut().for_each(|c| c.execute(contents));
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        self.commands
+            .iter_mut()
+            .rev()
+            .for_each(|c| c.undo(contents));
+    }
+
+    fn as_group(&mut self) -> Option<&mut CommandGroup> {
+        Some(self)
+    }
 }
 
 #[derive(Debug)]
@@ -72,7 +331,110 @@ impl Command for DrawLine {
     }
 
     fn undo(&mut self, contents: &mut Contents) {
-        contents.lines.pop();
+        contents.lines.retain(|l| l.id != self.line.id);
+    }
+}
+
+#[derive(Debug)]
+pub struct RemoveLines {
+    lines: Vec<Line>,
+}
+
+impl RemoveLines {
+    pub fn new(lines: Vec<Line>) -> Self {
+        Self { lines }
+    }
+}
+
+impl Command for RemoveLines {
+    fn execute(&mut self, contents: &mut Contents) {
+        contents
+            .lines
+            .retain(|l| !self.lines.iter().any(|r| r.id == l.id));
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        contents.lines.extend(self.lines.iter().cloned());
+    }
+}
+
+/// Replaces lines with what's left of them after erasing.
+#[derive(Debug)]
+pub struct SplitLines {
+    removed: Vec<Line>,
+    added: Vec<Line>,
+}
+
+impl SplitLines {
+    pub fn new(removed: Vec<Line>, added: Vec<Line>) -> Self {
+        Self { removed, added }
+    }
+}
+
+impl Command for SplitLines {
+    fn execute(&mut self, contents: &mut Contents) {
+        contents
+            .lines
+            .retain(|l| !self.removed.iter().any(|r| r.id == l.id));
+        contents.lines.extend(self.added.iter().cloned());
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        contents
+            .lines
+            .retain(|l| !self.added.iter().any(|a| a.id == l.id));
+        contents.lines.extend(self.removed.iter().cloned());
+    }
+}
+
+#[derive(Debug)]
+pub struct RemoveSelection {
+    removed: Snapshot,
+}
+
+impl RemoveSelection {
+    pub fn new(selection: &Selection, contents: &Contents) -> Self {
+        Self {
+            removed: selection.copy(contents),
+        }
+    }
+}
+
+impl Command for RemoveSelection {
+    fn execute(&mut self, contents: &mut Contents) {
+        let removed = &self.removed;
+        contents
+            .lines
+            .retain(|l| !removed.lines.iter().any(|r| r.id == l.id));
+        contents
+            .images
+            .retain(|i| !removed.images.iter().any(|r| r.id == i.id));
+        contents
+            .erasers
+            .retain(|e| !removed.erasers.iter().any(|r| r.id == e.id));
+        contents
+            .rects
+            .retain(|s| !removed.rects.iter().any(|r| r.id == s.id));
+        contents
+            .ellipses
+            .retain(|s| !removed.ellipses.iter().any(|r| r.id == s.id));
+        contents
+            .arrows
+            .retain(|s| !removed.arrows.iter().any(|r| r.id == s.id));
+        contents
+            .texts
+            .retain(|s| !removed.texts.iter().any(|r| r.id == s.id));
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        let removed = &self.removed;
+        contents.lines.extend(removed.lines.iter().cloned());
+        contents.images.extend(removed.images.iter().cloned());
+        contents.erasers.extend_from_slice(&removed.erasers);
+        contents.rects.extend_from_slice(&removed.rects);
+        contents.ellipses.extend_from_slice(&removed.ellipses);
+        contents.arrows.extend_from_slice(&removed.arrows);
+        contents.texts.extend(removed.texts.iter().cloned());
     }
 }
 
@@ -93,7 +455,8 @@ impl Command for PasteImage {
     }
 
     fn undo(&mut self, contents: &mut Contents) {
-        contents.images.pop();
+        // other commands can reorder images so the last one isn't necessarily ours
+        contents.images.retain(|i| i.id != self.image.id);
     }
 }
 
@@ -184,23 +547,401 @@ impl Command for MoveImage {
     }
 }
 
+#[derive(Debug)]
+pub struct ChangeLayer {
+    id: ImageId,
+    start_z: usize,
+    end_z: usize,
+}
+
+impl ChangeLayer {
+    pub fn new(id: ImageId, start_z: usize, end_z: usize) -> Self {
+        Self { id, start_z, end_z }
+    }
+}
+
+impl Command for ChangeLayer {
+    fn execute(&mut self, contents: &mut Contents) {
+        if let Some(img) = contents.image(self.id) {
+            img.z = self.end_z;
+        }
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        if let Some(img) = contents.image(self.id) {
+            img.z = self.start_z;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MoveSelection {
+    selection: Selection,
+    offset: CanvasVector,
+}
+
+impl MoveSelection {
+    pub fn new(selection: Selection, offset: CanvasVector) -> Self {
+        Self { selection, offset }
+    }
+}
+
+impl Command for MoveSelection {
+    fn execute(&mut self, contents: &mut Contents) {
+        self.selection.translate(contents, self.offset);
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        self.selection.translate(contents, -self.offset);
+    }
+}
+
+/// Erasers added one after another, possibly in several gestures.
 #[derive(Debug)]
 pub struct AddEraser {
-    eraser: Eraser,
+    erasers: Vec<Eraser>,
 }
 
 impl AddEraser {
     pub fn new(eraser: Eraser) -> Self {
-        Self { eraser }
+        Self {
+            erasers: vec![eraser],
+        }
     }
 }
 
 impl Command for AddEraser {
     fn execute(&mut self, contents: &mut Contents) {
-        contents.erasers.push(self.eraser);
+        contents.erasers.extend_from_slice(&self.erasers);
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        // removing and restoring a selection can reorder erasers
+        contents
+            .erasers
+            .retain(|e| !self.erasers.iter().any(|r| r.id == e.id));
+    }
+
+    fn as_eraser(&mut self) -> Option<&mut AddEraser> {
+        Some(self)
+    }
+}
+
+#[derive(Debug)]
+pub struct DrawRect {
+    rect: Rectangle,
+}
+
+impl DrawRect {
+    pub fn new(rect: Rectangle) -> Self {
+        Self { rect }
+    }
+}
+
+impl Command for DrawRect {
+    fn execute(&mut self, contents: &mut Contents) {
+        contents.rects.push(self.rect);
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        contents.rects.retain(|r| r.id != self.rect.id);
+    }
+}
+
+#[derive(Debug)]
+pub struct DrawEllipse {
+    ellipse: Ellipse,
+}
+
+impl DrawEllipse {
+    pub fn new(ellipse: Ellipse) -> Self {
+        Self { ellipse }
+    }
+}
+
+impl Command for DrawEllipse {
+    fn execute(&mut self, contents: &mut Contents) {
+        contents.ellipses.push(self.ellipse);
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        contents.ellipses.retain(|e| e.id != self.ellipse.id);
+    }
+}
+
+#[derive(Debug)]
+pub struct DrawArrow {
+    arrow: Arrow,
+}
+
+impl DrawArrow {
+    pub fn new(arrow: Arrow) -> Self {
+        Self { arrow }
+    }
+}
+
+impl Command for DrawArrow {
+    fn execute(&mut self, contents: &mut Contents) {
+        contents.arrows.push(self.arrow);
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        contents.arrows.retain(|a| a.id != self.arrow.id);
+    }
+}
+
+#[derive(Debug)]
+pub struct AddText {
+    text: Text,
+}
+
+impl AddText {
+    pub fn new(text: Text) -> Self {
+        Self { text }
+    }
+}
+
+impl Command for AddText {
+    fn execute(&mut self, contents: &mut Contents) {
+        contents.texts.push(self.text.clone());
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        contents.texts.retain(|t| t.id != self.text.id);
+    }
+}
+
+/// Empties the canvas, ids keep counting up so new ones never clash with
+/// the cleared ones coming back.
+#[derive(Debug, Default)]
+pub struct ClearAll {
+    cleared: Option<Snapshot>,
+}
+
+impl Command for ClearAll {
+    fn execute(&mut self, contents: &mut Contents) {
+        self.cleared = Some(contents.restore(Snapshot::default()));
+    }
+
+    fn undo(&mut self, contents: &mut Contents) {
+        if let Some(cleared) = self.cleared.take() {
+            contents.restore(cleared);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RestoreCheckpoint {
+    // the drawing to swap in, after executing it's the one swapped out
+    snapshot: Option<Snapshot>,
+}
+
+impl RestoreCheckpoint {
+    pub fn new(snapshot: Snapshot) -> Self {
+        Self {
+            snapshot: Some(snapshot),
+        }
+    }
+
+    fn swap(&mut self, contents: &mut Contents) {
+        let snapshot = self.snapshot.take().unwrap();
+        self.snapshot = Some(contents.restore(snapshot));
+    }
+}
+
+impl Command for RestoreCheckpoint {
+    fn execute(&mut self, contents: &mut Contents) {
+        self.swap(contents);
     }
 
     fn undo(&mut self, contents: &mut Contents) {
-        contents.erasers.pop();
+        self.swap(contents);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{mem, rc::Rc};
+
+    use raylib::{color::Color, ffi, texture::Texture2D};
+
+    use super::*;
+    use crate::{
+        config::Config,
+        graphics::{Brush, BrushMode},
+    };
+
+    fn line(contents: &mut Contents) -> Line {
+        let brush = Brush {
+            color: Color::WHITE,
+            thickness: CanvasLength::new(1.0),
+            mode: BrushMode::Pen,
+        };
+        let id = contents.next_line_id();
+        Line::new(CanvasPoint::origin(), brush, id, 0)
+    }
+
+    fn image(contents: &mut Contents) -> Image {
+        let texture = unsafe {
+            Texture2D::from_raw(ffi::Texture2D {
+                id: 0,
+                width: 1,
+                height: 1,
+                mipmaps: 1,
+                format: ffi::PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8A8 as i32,
+            })
+        };
+        let id = contents.next_image_id();
+        let image = Image::new(
+            CanvasPoint::origin(),
+            texture,
+            CanvasLength::new(1.0),
+            id,
+            0,
+            &Config::default(),
+        );
+        // never uploaded, so it mustn't get unloaded without a window either
+        mem::forget(Rc::clone(&image.texture));
+        image
+    }
+
+    fn paste(invoker: &mut CommandInvoker, contents: &mut Contents) -> ImageId {
+        let image = image(contents);
+        let id = image.id;
+        contents.images.push(image.clone());
+        invoker.push(PasteImage::new(image));
+        id
+    }
+
+    /// Draws a line the way the drawing states do, by adding it and then
+    /// pushing the command.
+    fn draw(invoker: &mut CommandInvoker, contents: &mut Contents) -> Line {
+        let line = line(contents);
+        contents.lines.push(line.clone());
+        invoker.push(DrawLine::new(line.clone()));
+        line
+    }
+
+    #[test]
+    fn push_after_undo_clears_redo() {
+        let mut contents = Contents::new();
+        let mut invoker = CommandInvoker::new(10, Duration::ZERO);
+
+        draw(&mut invoker, &mut contents);
+        assert!(invoker.undo(&mut contents));
+        let b = draw(&mut invoker, &mut contents);
+
+        assert_eq!(invoker.redo_len(), 0);
+        assert!(!invoker.redo(&mut contents));
+        assert_eq!(
+            contents.lines.iter().map(|l| l.id).collect::<Vec<_>>(),
+            [b.id]
+        );
+    }
+
+    #[test]
+    fn quick_pushes_are_undone_together() {
+        let mut contents = Contents::new();
+        let mut invoker = CommandInvoker::new(10, Duration::from_secs(3600));
+
+        for _ in 0..3 {
+            draw(&mut invoker, &mut contents);
+        }
+
+        assert_eq!(invoker.undo_len(), 1);
+        assert!(invoker.undo(&mut contents));
+        assert!(contents.lines.is_empty());
+    }
+
+    #[test]
+    fn pushes_are_separate_without_grouping() {
+        let mut contents = Contents::new();
+        let mut invoker = CommandInvoker::new(10, Duration::ZERO);
+
+        for _ in 0..3 {
+            draw(&mut invoker, &mut contents);
+        }
+
+        assert_eq!(invoker.undo_len(), 3);
+        assert!(invoker.undo(&mut contents));
+        assert_eq!(contents.lines.len(), 2);
+    }
+
+    #[test]
+    fn end_group_starts_a_new_group() {
+        let mut contents = Contents::new();
+        let mut invoker = CommandInvoker::new(10, Duration::from_secs(3600));
+
+        draw(&mut invoker, &mut contents);
+        draw(&mut invoker, &mut contents);
+        invoker.end_group();
+        let last = draw(&mut invoker, &mut contents);
+
+        assert_eq!(invoker.undo_len(), 2);
+        assert!(invoker.undo(&mut contents));
+        assert_eq!(contents.lines.len(), 2);
+        assert!(contents.lines.iter().all(|l| l.id != last.id));
+    }
+
+    #[test]
+    fn pasted_image_ids_stay_unique_through_undo() {
+        let mut contents = Contents::new();
+        let mut invoker = CommandInvoker::new(10, Duration::ZERO);
+
+        let a = paste(&mut invoker, &mut contents);
+        let b = paste(&mut invoker, &mut contents);
+        assert!(invoker.undo(&mut contents));
+        let c = paste(&mut invoker, &mut contents);
+
+        let ids = contents.images.iter().map(|i| i.id).collect::<Vec<_>>();
+        assert_eq!(ids, [a, c]);
+        assert_ne!(c, b);
+
+        let to = CanvasPoint::new(10.0, 10.0);
+        let mut moved = MoveImage::new(c, CanvasPoint::origin(), to);
+        moved.execute(&mut contents);
+        assert_eq!(contents.image(c).unwrap().pos, to);
+        assert_eq!(contents.image(a).unwrap().pos, CanvasPoint::origin());
+
+        moved.undo(&mut contents);
+        assert_eq!(contents.image(c).unwrap().pos, CanvasPoint::origin());
+    }
+
+    #[test]
+    fn timeline_replays_from_the_start_without_undone_strokes() {
+        let mut contents = Contents::new();
+        let mut invoker = CommandInvoker::new(10, Duration::ZERO);
+        let loaded = line(&mut contents);
+        contents.lines.push(loaded.clone());
+        let mut timeline = Timeline::new(&contents, invoker.changes());
+
+        let a = draw(&mut invoker, &mut contents);
+        timeline.record(&contents, invoker.changes());
+        draw(&mut invoker, &mut contents);
+        timeline.record(&contents, invoker.changes());
+        invoker.undo(&mut contents);
+        timeline.record(&contents, invoker.changes());
+
+        let (start, strokes) = timeline.replay(&contents);
+        let ids = |s: &Snapshot| s.lines.iter().map(|l| l.id).collect::<Vec<_>>();
+        assert_eq!(ids(&start), [loaded.id]);
+        assert_eq!(strokes.len(), 1);
+        assert_eq!(ids(&strokes[0].added), [a.id]);
+    }
+
+    #[test]
+    fn smaller_limits_drop_the_oldest_commands() {
+        let mut contents = Contents::new();
+        let mut invoker = CommandInvoker::new(10, Duration::ZERO);
+        for _ in 0..5 {
+            draw(&mut invoker, &mut contents);
+        }
+        invoker.undo(&mut contents);
+
+        invoker.set_limits(2, Duration::ZERO);
+        assert_eq!(invoker.undo_len(), 2);
+        assert_eq!(invoker.redo_len(), 1);
+        assert!(invoker.redo(&mut contents));
+        assert_eq!(invoker.undo_len(), 2);
     }
 }
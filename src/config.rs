@@ -1,22 +1,118 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, env, fs, io, path::PathBuf};
 
 use configparser::ini::Ini;
-use raylib::{color::Color, ffi::KeyboardKey};
+use raylib::{
+    color::Color,
+    ffi::{KeyboardKey, MouseButton, TextureFilter},
+};
 
-use crate::input::{Action, Keybind};
+use crate::input::{Action, Key, Keybind, Modifier};
 
 const DEFAULT_CONFIG: &str = include_str!("../kajet.conf");
 
+/// Trades stroke smoothness for drawing speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    // straight segments without joints
+    Low,
+    Medium,
+    // also rounds the ends of smooth strokes
+    High,
+}
+
+/// What erasing does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EraserMode {
+    // paints over a rectangle with the background color
+    Area,
+    // removes whole lines
+    Stroke,
+    // removes the parts of lines under the cursor
+    Pixel,
+}
+
+/// What scrolling does in the middle of a stroke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawingScroll {
+    Nothing,
+    Zoom,
+    Thickness,
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub thickness: f32,
+    pub snap_thickness: bool,
+    pub polyline: bool,
+    pub lazy_radius: f32,
+    pub straighten: f32,
+    pub recognize_shapes: bool,
+    pub text_size: f32,
+    pub drawing_scroll: DrawingScroll,
+    pub auto_advance_color: bool,
     pub fps: u32,
+    pub exit_key: Option<KeyboardKey>,
+    pub monitor: u32,
+    pub window_size: f32,
+    // None picks it from the display DPI
+    pub ui_scale: Option<f32>,
+    pub transparent: bool,
     pub show_fps: bool,
+    pub quality: Quality,
+    pub show_rulers: bool,
+    pub scale_bar: bool,
+    pub show_status: bool,
+    pub vignette: bool,
+    pub palette_strip: bool,
+    pub reduce_motion: bool,
     pub scroll_sensitivity: f32,
+    pub playback_speed: f32,
+    pub invert_scroll: bool,
+    pub smart_guides: bool,
+    pub adaptive_zoom: bool,
+    pub zoom: (f32, f32),
+    pub snap_spacing: f32,
+    pub snap_angle: f32,
+    pub drag_threshold: f32,
+    pub nudge_distance: f32,
+    pub pan_deadzone: f32,
+    pub pan_resistance: f32,
+    pub pan_momentum: bool,
+    pub absolute_input: bool,
+    pub context_menu: bool,
+    pub erase_button: Option<MouseButton>,
+    pub eraser: EraserMode,
+    pub eraser_radius: f32,
+    pub eraser_merge_time: u32,
     pub undo_buffer_size: usize,
+    pub undo_group_time: u32,
+    pub autosave_interval: u32,
+    pub autosave_debounce: u32,
+    pub max_image_size: u32,
+    pub palette_size: usize,
+    pub tracing_image: Option<String>,
+    pub tracing_opacity: f32,
+    pub text_file: String,
+    pub export_file: String,
+    pub transparent_background: bool,
+    // smallest and largest scale an image can be resized to
+    pub image_scale: (f32, f32),
+    pub mipmaps: bool,
+    pub texture_filter: TextureFilter,
+    pub notify_empty_paste: bool,
+    pub notify_empty_undo: bool,
     pub background: Color,
     pub colors: Box<[Color]>,
     pub keybinds: Box<[Keybind]>,
+    pub disable_snap: Modifier,
+    pub angle_snap: Modifier,
+    pub draw_rect: Modifier,
+    pub draw_arrow: Modifier,
+    pub select: Modifier,
+    pub erase: Modifier,
+    pub move_canvas: Modifier,
+    pub scroll_thickness: Modifier,
+    pub select_image: Modifier,
 }
 
 impl Default for Config {
@@ -35,33 +131,101 @@ impl Default for Config {
 
         Self {
             thickness: parse!(parse_thickness),
+            snap_thickness: parse!(parse_snap_thickness),
+            polyline: parse!(parse_polyline),
+            lazy_radius: parse!(parse_lazy_radius),
+            straighten: parse!(parse_straighten),
+            recognize_shapes: parse!(parse_recognize_shapes),
+            text_size: parse!(parse_text_size),
+            drawing_scroll: parse!(parse_drawing_scroll),
+            auto_advance_color: parse!(parse_auto_advance_color),
             fps: parse!(parse_fps),
+            exit_key: parse!(parse_exit_key),
+            monitor: parse!(parse_monitor),
+            window_size: parse!(parse_window_size),
+            transparent: parse!(parse_transparent),
+            ui_scale: parse!(parse_ui_scale),
             show_fps: parse!(parse_show_fps),
+            quality: parse!(parse_quality),
+            show_rulers: parse!(parse_show_rulers),
+            scale_bar: parse!(parse_scale_bar),
+            show_status: parse!(parse_show_status),
+            vignette: parse!(parse_vignette),
+            palette_strip: parse!(parse_palette_strip),
+            reduce_motion: parse!(parse_reduce_motion),
             scroll_sensitivity: parse!(parse_scroll_sensitivity),
+            playback_speed: parse!(parse_playback_speed),
+            invert_scroll: parse!(parse_invert_scroll),
+            smart_guides: parse!(parse_smart_guides),
+            adaptive_zoom: parse!(parse_adaptive_zoom),
+            zoom: parse!(parse_zoom),
+            snap_spacing: parse!(parse_snap_spacing),
+            snap_angle: parse!(parse_snap_angle),
+            drag_threshold: parse!(parse_drag_threshold),
+            nudge_distance: parse!(parse_nudge_distance),
+            pan_deadzone: parse!(parse_pan_deadzone),
+            pan_resistance: parse!(parse_pan_resistance),
+            pan_momentum: parse!(parse_pan_momentum),
+            absolute_input: parse!(parse_absolute_input),
+            context_menu: parse!(parse_context_menu),
+            erase_button: parse!(parse_erase_button),
+            eraser: parse!(parse_eraser),
+            eraser_radius: parse!(parse_eraser_radius),
+            eraser_merge_time: parse!(parse_eraser_merge_time),
             undo_buffer_size: parse!(parse_undo_buffer_size),
+            undo_group_time: parse!(parse_undo_group_time),
+            autosave_interval: parse!(parse_autosave_interval),
+            autosave_debounce: parse!(parse_autosave_debounce),
+            max_image_size: parse!(parse_max_image_size),
+            palette_size: parse!(parse_palette_size),
+            tracing_image: parse!(parse_tracing_image),
+            tracing_opacity: parse!(parse_tracing_opacity),
+            text_file: parse!(parse_text_file),
+            export_file: parse!(parse_export_file),
+            transparent_background: parse!(parse_transparent_background),
+            image_scale: parse!(parse_image_scale),
+            mipmaps: parse!(parse_mipmaps),
+            texture_filter: parse!(parse_texture_filter),
+            notify_empty_paste: parse!(parse_notify_empty_paste),
+            notify_empty_undo: parse!(parse_notify_empty_undo),
             background: parse!(parse_background),
             colors: parse!(parse_colors),
             keybinds: parse!(parse_keybinds),
+            disable_snap: parse!(parse_disable_snap),
+            angle_snap: parse!(parse_angle_snap),
+            draw_rect: parse!(parse_draw_rect),
+            draw_arrow: parse!(parse_draw_arrow),
+            select: parse!(parse_select),
+            erase: parse!(parse_erase),
+            move_canvas: parse!(parse_move_canvas),
+            scroll_thickness: parse!(parse_scroll_thickness),
+            select_image: parse!(parse_select_image),
         }
     }
 }
 type ConfigMap = HashMap<String, HashMap<String, Option<String>>>;
 
+/// Where the config gets read from.
+#[derive(Debug, PartialEq)]
+enum ConfigSource {
+    // given on the command line or through KAJET_CONFIG
+    Given(String),
+    // created with the defaults if it doesn't exist
+    Default(PathBuf),
+}
+
 impl Config {
     pub fn from_file(path: Option<String>) -> Result<Self, String> {
         let default_file = DEFAULT_CONFIG.to_string();
-        let file = if let Some(path) = path {
-            match fs::read_to_string(&path) {
+        let file = match Self::source(path, env::var("KAJET_CONFIG").ok(), dirs::config_dir()) {
+            Some(ConfigSource::Given(path)) => match Self::read_given(&path) {
                 Ok(s) => s,
                 Err(_) => {
                     eprintln!("[ERROR] Couldn't read the config file {path}");
                     default_file
                 }
-            }
-        } else if let Some(mut path) = dirs::config_dir() {
-            path.push("kajet");
-            path.set_extension("conf");
-            match fs::read_to_string(&path) {
+            },
+            Some(ConfigSource::Default(path)) => match fs::read_to_string(&path) {
                 Ok(s) => s,
                 Err(_) => {
                     let path_str = path.to_str().unwrap();
@@ -73,13 +237,61 @@ impl Config {
                     };
                     default_file
                 }
-            }
-        } else {
-            default_file
+            },
+            None => default_file,
+        };
+
+        Self::from_text(file)
+    }
+
+    /// Reads the config like on startup, but fails instead of falling back
+    /// to the defaults when the file can't be read.
+    pub fn reload(path: Option<String>) -> Result<Self, String> {
+        let file = match Self::source(path, env::var("KAJET_CONFIG").ok(), dirs::config_dir()) {
+            Some(ConfigSource::Given(path)) => Self::read_given(&path)
+                .map_err(|e| format!("Couldn't read the config file {path} ({e})"))?,
+            Some(ConfigSource::Default(path)) => fs::read_to_string(&path)
+                .map_err(|e| format!("Couldn't read the config file {} ({e})", path.display()))?,
+            None => return Err("There's no config file to reload".to_string()),
         };
 
+        Self::from_text(file)
+    }
+
+    /// An explicit path takes precedence over the environment, which takes
+    /// precedence over kajet.conf in the config directory.
+    fn source(
+        path: Option<String>,
+        env: Option<String>,
+        config_dir: Option<PathBuf>,
+    ) -> Option<ConfigSource> {
+        if let Some(path) = path.or(env) {
+            return Some(ConfigSource::Given(path));
+        }
+        let mut path = config_dir?;
+        path.push("kajet");
+        path.set_extension("conf");
+        Some(ConfigSource::Default(path))
+    }
+
+    // "-" reads from stdin
+    fn read_given(path: &str) -> io::Result<String> {
+        if path == "-" {
+            io::read_to_string(io::stdin())
+        } else {
+            fs::read_to_string(path)
+        }
+    }
+
+    pub fn clamp_image_scale(&self, scale: f32) -> f32 {
+        let (min, max) = self.image_scale;
+        scale.clamp(min, max)
+    }
+
+    /// Parses config contents given directly instead of through a file.
+    pub fn from_text(text: String) -> Result<Self, String> {
         let mut conf = Ini::new();
-        let map = conf.read(file)?;
+        let map = conf.read(text)?;
 
         Ok(Self::from_string(&map))
     }
@@ -98,13 +310,75 @@ impl Config {
 
         Self {
             thickness: parse!(thickness, parse_thickness),
+            snap_thickness: parse!(snap_thickness, parse_snap_thickness),
+            polyline: parse!(polyline, parse_polyline),
+            lazy_radius: parse!(lazy_radius, parse_lazy_radius),
+            straighten: parse!(straighten, parse_straighten),
+            recognize_shapes: parse!(recognize_shapes, parse_recognize_shapes),
+            text_size: parse!(text_size, parse_text_size),
+            drawing_scroll: parse!(drawing_scroll, parse_drawing_scroll),
+            auto_advance_color: parse!(auto_advance_color, parse_auto_advance_color),
             fps: parse!(fps, parse_fps),
+            exit_key: parse!(exit_key, parse_exit_key),
+            monitor: parse!(monitor, parse_monitor),
+            window_size: parse!(window_size, parse_window_size),
+            transparent: parse!(transparent, parse_transparent),
+            ui_scale: parse!(ui_scale, parse_ui_scale),
             show_fps: parse!(show_fps, parse_show_fps),
+            quality: parse!(quality, parse_quality),
+            show_rulers: parse!(show_rulers, parse_show_rulers),
+            scale_bar: parse!(scale_bar, parse_scale_bar),
+            show_status: parse!(show_status, parse_show_status),
+            vignette: parse!(vignette, parse_vignette),
+            palette_strip: parse!(palette_strip, parse_palette_strip),
+            reduce_motion: parse!(reduce_motion, parse_reduce_motion),
             scroll_sensitivity: parse!(scroll_sensitivity, parse_scroll_sensitivity),
+            playback_speed: parse!(playback_speed, parse_playback_speed),
+            invert_scroll: parse!(invert_scroll, parse_invert_scroll),
+            smart_guides: parse!(smart_guides, parse_smart_guides),
+            adaptive_zoom: parse!(adaptive_zoom, parse_adaptive_zoom),
+            zoom: parse!(zoom, parse_zoom),
+            snap_spacing: parse!(snap_spacing, parse_snap_spacing),
+            snap_angle: parse!(snap_angle, parse_snap_angle),
+            drag_threshold: parse!(drag_threshold, parse_drag_threshold),
+            nudge_distance: parse!(nudge_distance, parse_nudge_distance),
+            pan_deadzone: parse!(pan_deadzone, parse_pan_deadzone),
+            pan_resistance: parse!(pan_resistance, parse_pan_resistance),
+            pan_momentum: parse!(pan_momentum, parse_pan_momentum),
+            absolute_input: parse!(absolute_input, parse_absolute_input),
+            context_menu: parse!(context_menu, parse_context_menu),
+            erase_button: parse!(erase_button, parse_erase_button),
+            eraser: parse!(eraser, parse_eraser),
+            eraser_radius: parse!(eraser_radius, parse_eraser_radius),
+            eraser_merge_time: parse!(eraser_merge_time, parse_eraser_merge_time),
             undo_buffer_size: parse!(undo_buffer_size, parse_undo_buffer_size),
+            undo_group_time: parse!(undo_group_time, parse_undo_group_time),
+            autosave_interval: parse!(autosave_interval, parse_autosave_interval),
+            autosave_debounce: parse!(autosave_debounce, parse_autosave_debounce),
+            max_image_size: parse!(max_image_size, parse_max_image_size),
+            palette_size: parse!(palette_size, parse_palette_size),
+            tracing_image: parse!(tracing_image, parse_tracing_image),
+            tracing_opacity: parse!(tracing_opacity, parse_tracing_opacity),
+            text_file: parse!(text_file, parse_text_file),
+            export_file: parse!(export_file, parse_export_file),
+            transparent_background: parse!(transparent_background, parse_transparent_background),
+            image_scale: parse!(image_scale, parse_image_scale),
+            mipmaps: parse!(mipmaps, parse_mipmaps),
+            texture_filter: parse!(texture_filter, parse_texture_filter),
+            notify_empty_paste: parse!(notify_empty_paste, parse_notify_empty_paste),
+            notify_empty_undo: parse!(notify_empty_undo, parse_notify_empty_undo),
             background: parse!(background, parse_background),
             colors: parse!(colors, parse_colors),
             keybinds: parse!(keybinds, parse_keybinds),
+            disable_snap: parse!(disable_snap, parse_disable_snap),
+            angle_snap: parse!(angle_snap, parse_angle_snap),
+            draw_rect: parse!(draw_rect, parse_draw_rect),
+            draw_arrow: parse!(draw_arrow, parse_draw_arrow),
+            select: parse!(select, parse_select),
+            erase: parse!(erase, parse_erase),
+            move_canvas: parse!(move_canvas, parse_move_canvas),
+            scroll_thickness: parse!(scroll_thickness, parse_scroll_thickness),
+            select_image: parse!(select_image, parse_select_image),
         }
     }
 
@@ -117,11 +391,68 @@ impl Config {
             .ok_or(format!("Expected '{key}' value"))
     }
 
+    fn parse_bool(map: &ConfigMap, section: &str, key: &str) -> Result<bool, String> {
+        let value = Self::get_value(map, section, key)?;
+        match value.to_lowercase().as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            s => Err(format!("{key} should be either true or false, got {s}")),
+        }
+    }
+
     fn parse_color(color: &str) -> Result<Color, String> {
-        let s = color.trim_start_matches("0x");
+        let s = Self::color_name(color).unwrap_or(color.trim_start_matches("0x"));
         Color::from_hex(s).map_err(|e| e.to_string())
     }
 
+    /// The hex value of a CSS color name.
+    fn color_name(name: &str) -> Option<&'static str> {
+        match name.trim().to_lowercase().as_str() {
+            "black" => Some("000000"),
+            "white" => Some("ffffff"),
+            "gray" => Some("808080"),
+            "grey" => Some("808080"),
+            "silver" => Some("c0c0c0"),
+            "lightgray" => Some("d3d3d3"),
+            "darkgray" => Some("a9a9a9"),
+            "red" => Some("ff0000"),
+            "darkred" => Some("8b0000"),
+            "crimson" => Some("dc143c"),
+            "maroon" => Some("800000"),
+            "orange" => Some("ffa500"),
+            "darkorange" => Some("ff8c00"),
+            "gold" => Some("ffd700"),
+            "yellow" => Some("ffff00"),
+            "lime" => Some("00ff00"),
+            "green" => Some("008000"),
+            "darkgreen" => Some("006400"),
+            "olive" => Some("808000"),
+            "teal" => Some("008080"),
+            "cyan" => Some("00ffff"),
+            "aqua" => Some("00ffff"),
+            "skyblue" => Some("87ceeb"),
+            "cornflowerblue" => Some("6495ed"),
+            "blue" => Some("0000ff"),
+            "navy" => Some("000080"),
+            "royalblue" => Some("4169e1"),
+            "purple" => Some("800080"),
+            "violet" => Some("ee82ee"),
+            "magenta" => Some("ff00ff"),
+            "fuchsia" => Some("ff00ff"),
+            "pink" => Some("ffc0cb"),
+            "hotpink" => Some("ff69b4"),
+            "brown" => Some("a52a2a"),
+            "chocolate" => Some("d2691e"),
+            "beige" => Some("f5f5dc"),
+            "tomato" => Some("ff6347"),
+            "coral" => Some("ff7f50"),
+            "salmon" => Some("fa8072"),
+            "indigo" => Some("4b0082"),
+            "turquoise" => Some("40e0d0"),
+            _ => None,
+        }
+    }
+
     fn parse_thickness(map: &ConfigMap) -> Result<f32, String> {
         let thickness = Self::get_value(map, "brush", "thickness")?;
         let thickness = match thickness.parse::<f32>() {
@@ -135,6 +466,73 @@ impl Config {
         }
     }
 
+    fn parse_snap_thickness(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "brush", "snap_thickness")
+    }
+
+    fn parse_polyline(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "brush", "polyline")
+    }
+
+    fn parse_auto_advance_color(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "auto_advance_color")
+    }
+
+    fn parse_lazy_radius(map: &ConfigMap) -> Result<f32, String> {
+        let radius = Self::get_value(map, "brush", "lazy_radius")?;
+        let radius = match radius.parse::<f32>() {
+            Ok(r) => Ok(r),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if radius < 0.0 {
+            Err(format!("Lazy radius should be >= 0.0, got {radius}"))
+        } else {
+            Ok(radius)
+        }
+    }
+
+    fn parse_straighten(map: &ConfigMap) -> Result<f32, String> {
+        let deviation = Self::get_value(map, "brush", "straighten")?;
+        let deviation = match deviation.parse::<f32>() {
+            Ok(d) => Ok(d),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if deviation < 0.0 {
+            Err(format!("Straighten should be >= 0.0, got {deviation}"))
+        } else {
+            Ok(deviation)
+        }
+    }
+
+    fn parse_recognize_shapes(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "brush", "recognize_shapes")
+    }
+
+    fn parse_text_size(map: &ConfigMap) -> Result<f32, String> {
+        let size = Self::get_value(map, "brush", "text_size")?;
+        let size = match size.parse::<f32>() {
+            Ok(s) => Ok(s),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if size <= 0.0 {
+            Err(format!("Text size should be > 0.0, got {size}"))
+        } else {
+            Ok(size)
+        }
+    }
+
+    fn parse_drawing_scroll(map: &ConfigMap) -> Result<DrawingScroll, String> {
+        let scroll = Self::get_value(map, "brush", "scroll_while_drawing")?;
+        match scroll.to_lowercase().as_str() {
+            "none" | "nothing" => Ok(DrawingScroll::Nothing),
+            "zoom" => Ok(DrawingScroll::Zoom),
+            "thickness" => Ok(DrawingScroll::Thickness),
+            _ => Err(format!(
+                "Scroll while drawing should be one of none, zoom or thickness, got {scroll}"
+            )),
+        }
+    }
+
     fn parse_fps(map: &ConfigMap) -> Result<u32, String> {
         let fps = Self::get_value(map, "other", "fps")?;
         let fps = match fps.parse::<u32>() {
@@ -148,15 +546,102 @@ impl Config {
         }
     }
 
+    fn parse_exit_key(map: &ConfigMap) -> Result<Option<KeyboardKey>, String> {
+        let key = Self::get_value(map, "other", "exit_key")?;
+        if key.to_lowercase() == "none" {
+            return Ok(None);
+        }
+
+        Self::parse_key(&key)
+            .map(Some)
+            .ok_or(format!("Invalid exit key {key}"))
+    }
+
+    fn parse_monitor(map: &ConfigMap) -> Result<u32, String> {
+        let monitor = Self::get_value(map, "window", "monitor")?;
+        match monitor.parse::<u32>() {
+            Ok(m) => Ok(m),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn parse_window_size(map: &ConfigMap) -> Result<f32, String> {
+        let size = Self::get_value(map, "window", "size")?;
+        let size = match size.parse::<f32>() {
+            Ok(s) => Ok(s),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if !(0.1..=1.0).contains(&size) {
+            Err(format!(
+                "Window size should be between 0.1 and 1.0, got {size}"
+            ))
+        } else {
+            Ok(size)
+        }
+    }
+
+    fn parse_transparent(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "window", "transparent")
+    }
+
+    fn parse_ui_scale(map: &ConfigMap) -> Result<Option<f32>, String> {
+        let scale = Self::get_value(map, "window", "ui_scale")?;
+        if scale.eq_ignore_ascii_case("auto") {
+            return Ok(None);
+        }
+        let scale = match scale.parse::<f32>() {
+            Ok(s) => Ok(s),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if !(0.5..=4.0).contains(&scale) {
+            Err(format!(
+                "UI scale should be auto or between 0.5 and 4.0, got {scale}"
+            ))
+        } else {
+            Ok(Some(scale))
+        }
+    }
+
     fn parse_show_fps(map: &ConfigMap) -> Result<bool, String> {
-        let show_fps = Self::get_value(map, "other", "show_fps")?;
-        match show_fps.to_lowercase().as_str() {
-            "true" => Ok(true),
-            "false" => Ok(false),
-            s => Err(format!("show_fps should be either true or false, got {s}")),
+        Self::parse_bool(map, "other", "show_fps")
+    }
+
+    fn parse_quality(map: &ConfigMap) -> Result<Quality, String> {
+        let quality = Self::get_value(map, "other", "quality")?;
+        match quality.to_lowercase().as_str() {
+            "low" => Ok(Quality::Low),
+            "medium" => Ok(Quality::Medium),
+            "high" => Ok(Quality::High),
+            _ => Err(format!(
+                "Quality should be one of low, medium or high, got {quality}"
+            )),
         }
     }
 
+    fn parse_show_rulers(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "show_rulers")
+    }
+
+    fn parse_scale_bar(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "scale_bar")
+    }
+
+    fn parse_show_status(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "show_status")
+    }
+
+    fn parse_vignette(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "vignette")
+    }
+
+    fn parse_palette_strip(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "palette_strip")
+    }
+
+    fn parse_reduce_motion(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "reduce_motion")
+    }
+
     fn parse_scroll_sensitivity(map: &ConfigMap) -> Result<f32, String> {
         let scroll_sensitivity = Self::get_value(map, "other", "scroll_sensitivity")?;
         let scroll_sensitivity = match scroll_sensitivity.parse::<f32>() {
@@ -172,6 +657,187 @@ impl Config {
         }
     }
 
+    fn parse_playback_speed(map: &ConfigMap) -> Result<f32, String> {
+        let speed = Self::get_value(map, "other", "playback_speed")?;
+        let speed = match speed.parse::<f32>() {
+            Ok(t) => Ok(t),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if speed <= 0.0 {
+            Err(format!("Playback speed should be > 0.0, got {speed}"))
+        } else {
+            Ok(speed)
+        }
+    }
+
+    fn parse_invert_scroll(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "invert_scroll")
+    }
+
+    fn parse_smart_guides(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "smart_guides")
+    }
+
+    fn parse_adaptive_zoom(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "adaptive_zoom")
+    }
+
+    fn parse_zoom(map: &ConfigMap) -> Result<(f32, f32), String> {
+        let parse = |key| {
+            let zoom = Self::get_value(map, "other", key)?;
+            match zoom.parse::<f32>() {
+                Ok(z) if z > 0.0 => Ok(z),
+                Ok(z) => Err(format!("Zoom should be > 0.0, got {z}")),
+                Err(e) => Err(e.to_string()),
+            }
+        };
+        let min = parse("min_zoom")?;
+        let max = parse("max_zoom")?;
+        if max <= min {
+            Err(format!("Max zoom should be > min zoom, got {max} <= {min}"))
+        } else {
+            Ok((min, max))
+        }
+    }
+
+    fn parse_absolute_input(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "absolute_input")
+    }
+
+    fn parse_pan_deadzone(map: &ConfigMap) -> Result<f32, String> {
+        let deadzone = Self::get_value(map, "other", "pan_deadzone")?;
+        let deadzone = match deadzone.parse::<f32>() {
+            Ok(d) => Ok(d),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if deadzone < 0.0 {
+            Err(format!("Pan deadzone should be >= 0.0, got {deadzone}"))
+        } else {
+            Ok(deadzone)
+        }
+    }
+
+    fn parse_pan_resistance(map: &ConfigMap) -> Result<f32, String> {
+        let resistance = Self::get_value(map, "other", "pan_resistance")?;
+        let resistance = match resistance.parse::<f32>() {
+            Ok(r) => Ok(r),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if resistance < 0.0 {
+            Err(format!("Pan resistance should be >= 0.0, got {resistance}"))
+        } else {
+            Ok(resistance)
+        }
+    }
+
+    fn parse_pan_momentum(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "pan_momentum")
+    }
+
+    fn parse_context_menu(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "context_menu")
+    }
+
+    fn parse_eraser(map: &ConfigMap) -> Result<EraserMode, String> {
+        let eraser = Self::get_value(map, "other", "eraser")?;
+        match eraser.to_lowercase().as_str() {
+            "area" => Ok(EraserMode::Area),
+            "stroke" => Ok(EraserMode::Stroke),
+            "pixel" => Ok(EraserMode::Pixel),
+            _ => Err(format!(
+                "Eraser should be one of area, stroke or pixel, got {eraser}"
+            )),
+        }
+    }
+
+    fn parse_eraser_radius(map: &ConfigMap) -> Result<f32, String> {
+        let radius = Self::get_value(map, "other", "eraser_radius")?;
+        let radius = match radius.parse::<f32>() {
+            Ok(r) => Ok(r),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if radius <= 0.0 {
+            Err(format!("Eraser radius should be > 0.0, got {radius}"))
+        } else {
+            Ok(radius)
+        }
+    }
+
+    fn parse_erase_button(map: &ConfigMap) -> Result<Option<MouseButton>, String> {
+        let button = Self::get_value(map, "other", "erase_button")?;
+        let button = match button.to_lowercase().as_str() {
+            "none" => return Ok(None),
+            "middle" => MouseButton::MOUSE_BUTTON_MIDDLE,
+            "side" | "back" => MouseButton::MOUSE_BUTTON_SIDE,
+            "extra" | "forward" => MouseButton::MOUSE_BUTTON_EXTRA,
+            // these already draw and move the camera
+            "left" | "right" => {
+                return Err(format!(
+                    "Erase button can't be {button} as it's already in use"
+                ));
+            }
+            _ => return Err(format!("Unknown mouse button {button}")),
+        };
+
+        Ok(Some(button))
+    }
+
+    fn parse_snap_spacing(map: &ConfigMap) -> Result<f32, String> {
+        let spacing = Self::get_value(map, "snap", "spacing")?;
+        let spacing = match spacing.parse::<f32>() {
+            Ok(s) => Ok(s),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if spacing < 0.0 {
+            Err(format!("Snap spacing should be >= 0.0, got {spacing}"))
+        } else {
+            Ok(spacing)
+        }
+    }
+
+    fn parse_snap_angle(map: &ConfigMap) -> Result<f32, String> {
+        let angle = Self::get_value(map, "snap", "angle")?;
+        let angle = match angle.parse::<f32>() {
+            Ok(a) => Ok(a),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if angle <= 0.0 || angle > 180.0 {
+            Err(format!("Snap angle should be in (0, 180], got {angle}"))
+        } else {
+            Ok(angle)
+        }
+    }
+
+    fn parse_drag_threshold(map: &ConfigMap) -> Result<f32, String> {
+        let drag_threshold = Self::get_value(map, "other", "drag_threshold")?;
+        let drag_threshold = match drag_threshold.parse::<f32>() {
+            Ok(t) => Ok(t),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if drag_threshold < 0.0 {
+            Err(format!(
+                "Drag threshold should be >= 0.0, got {drag_threshold}"
+            ))
+        } else {
+            Ok(drag_threshold)
+        }
+    }
+
+    fn parse_nudge_distance(map: &ConfigMap) -> Result<f32, String> {
+        let nudge_distance = Self::get_value(map, "other", "nudge_distance")?;
+        let nudge_distance = match nudge_distance.parse::<f32>() {
+            Ok(t) => Ok(t),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if nudge_distance <= 0.0 {
+            Err(format!(
+                "Nudge distance should be > 0.0, got {nudge_distance}"
+            ))
+        } else {
+            Ok(nudge_distance)
+        }
+    }
+
     fn parse_undo_buffer_size(map: &ConfigMap) -> Result<usize, String> {
         let undo_buffer_size = Self::get_value(map, "other", "undo_buffer_size")?;
         let undo_buffer_size = match undo_buffer_size.parse::<usize>() {
@@ -184,12 +850,15 @@ impl Config {
     fn parse_colors(map: &ConfigMap) -> Result<Box<[Color]>, String> {
         let mut colors = Vec::<Color>::new();
 
-        for i in 0..=9 {
-            let name = format!("color{i}");
-            if !map["colors"].contains_key(&name) {
-                continue;
-            }
-            let color = Self::get_value(map, "colors", &name)?;
+        // colorN keys in order of N, skipped numbers are fine
+        let mut names = map["colors"]
+            .keys()
+            .filter_map(|k| Some((k.strip_prefix("color")?.parse::<usize>().ok()?, k)))
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+
+        for (_, name) in names {
+            let color = Self::get_value(map, "colors", name)?;
             colors.push(Self::parse_color(&color)?);
         }
 
@@ -200,6 +869,150 @@ impl Config {
         }
     }
 
+    fn parse_eraser_merge_time(map: &ConfigMap) -> Result<u32, String> {
+        let time = Self::get_value(map, "other", "eraser_merge_time")?;
+        match time.parse::<u32>() {
+            Ok(t) => Ok(t),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn parse_undo_group_time(map: &ConfigMap) -> Result<u32, String> {
+        let time = Self::get_value(map, "other", "undo_group_time")?;
+        match time.parse::<u32>() {
+            Ok(t) => Ok(t),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn parse_autosave_interval(map: &ConfigMap) -> Result<u32, String> {
+        let interval = Self::get_value(map, "other", "autosave_interval")?;
+        match interval.parse::<u32>() {
+            Ok(i) => Ok(i),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn parse_autosave_debounce(map: &ConfigMap) -> Result<u32, String> {
+        let debounce = Self::get_value(map, "other", "autosave_debounce")?;
+        match debounce.parse::<u32>() {
+            Ok(d) => Ok(d),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn parse_max_image_size(map: &ConfigMap) -> Result<u32, String> {
+        let size = Self::get_value(map, "other", "max_image_size")?;
+        let size = match size.parse::<u32>() {
+            Ok(s) => Ok(s),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if size < 16 {
+            Err(format!("Max image size should be >= 16, got {size}"))
+        } else {
+            Ok(size)
+        }
+    }
+
+    fn parse_palette_size(map: &ConfigMap) -> Result<usize, String> {
+        let size = Self::get_value(map, "other", "palette_size")?;
+        let size = match size.parse::<usize>() {
+            Ok(s) => Ok(s),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if size == 0 {
+            Err("Palette size should be > 0".to_string())
+        } else {
+            Ok(size)
+        }
+    }
+
+    fn parse_tracing_image(map: &ConfigMap) -> Result<Option<String>, String> {
+        let path = Self::get_value(map, "other", "tracing_image")?;
+        Ok((!path.is_empty()).then_some(path))
+    }
+
+    fn parse_text_file(map: &ConfigMap) -> Result<String, String> {
+        let path = Self::get_value(map, "other", "text_file")?;
+        if path.is_empty() {
+            Err("text_file can't be empty".to_string())
+        } else {
+            Ok(path)
+        }
+    }
+
+    fn parse_export_file(map: &ConfigMap) -> Result<String, String> {
+        let path = Self::get_value(map, "other", "export_file")?;
+        if path.is_empty() {
+            Err("export_file can't be empty".to_string())
+        } else {
+            Ok(path)
+        }
+    }
+
+    fn parse_transparent_background(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "transparent_background")
+    }
+
+    fn parse_tracing_opacity(map: &ConfigMap) -> Result<f32, String> {
+        let opacity = Self::get_value(map, "other", "tracing_opacity")?;
+        let opacity = match opacity.parse::<f32>() {
+            Ok(o) => Ok(o),
+            Err(e) => Err(e.to_string()),
+        }?;
+        if !(0.0..=1.0).contains(&opacity) {
+            Err(format!(
+                "Tracing opacity should be between 0.0 and 1.0, got {opacity}"
+            ))
+        } else {
+            Ok(opacity)
+        }
+    }
+
+    fn parse_image_scale(map: &ConfigMap) -> Result<(f32, f32), String> {
+        let parse = |key| {
+            let scale = Self::get_value(map, "other", key)?;
+            match scale.parse::<f32>() {
+                Ok(s) if s > 0.0 => Ok(s),
+                Ok(s) => Err(format!("Image scale should be > 0.0, got {s}")),
+                Err(e) => Err(e.to_string()),
+            }
+        };
+        let min = parse("min_image_scale")?;
+        let max = parse("max_image_scale")?;
+        if max <= min {
+            Err(format!(
+                "Max image scale should be > min image scale, got {max} <= {min}"
+            ))
+        } else {
+            Ok((min, max))
+        }
+    }
+
+    fn parse_mipmaps(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "mipmaps")
+    }
+
+    fn parse_texture_filter(map: &ConfigMap) -> Result<TextureFilter, String> {
+        let filter = Self::get_value(map, "other", "texture_filter")?;
+        match filter.to_lowercase().as_str() {
+            "point" | "nearest" => Ok(TextureFilter::TEXTURE_FILTER_POINT),
+            "bilinear" => Ok(TextureFilter::TEXTURE_FILTER_BILINEAR),
+            "trilinear" => Ok(TextureFilter::TEXTURE_FILTER_TRILINEAR),
+            _ => Err(format!(
+                "Texture filter should be one of point, bilinear or trilinear, got {filter}"
+            )),
+        }
+    }
+
+    fn parse_notify_empty_paste(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "notify_empty_paste")
+    }
+
+    fn parse_notify_empty_undo(map: &ConfigMap) -> Result<bool, String> {
+        Self::parse_bool(map, "other", "notify_empty_undo")
+    }
+
     fn parse_background(map: &ConfigMap) -> Result<Color, String> {
         let background = Self::get_value(map, "colors", "background")?;
         let background = Self::parse_color(&background)?;
@@ -213,21 +1026,12 @@ impl Config {
             for (action, keys) in binds {
                 let action = Self::parse_action(action);
                 if let (Some(action), Some(keys)) = (action, keys) {
-                    let mut key_combo = vec![];
-                    for keys in keys.split('|') {
-                        let keys: Option<Vec<_>> = keys.split('+').map(Self::parse_key).collect();
-                        let keys = match keys {
-                            Some(k) => k,
-                            None => continue,
-                        }
-                        .into_boxed_slice();
-                        key_combo.push(keys);
-                    }
+                    let key_combo = Self::parse_combos(keys);
                     if key_combo.is_empty() {
                         continue;
                     }
 
-                    keybinds.push(Keybind::new(key_combo.into_boxed_slice(), action));
+                    keybinds.push(Keybind::new(key_combo, action));
                 }
             }
         }
@@ -235,6 +1039,71 @@ impl Config {
         Ok(keybinds.into_boxed_slice())
     }
 
+    fn parse_combos(keys: &str) -> Box<[Box<[Key]>]> {
+        let mut key_combo = vec![];
+        for keys in keys.split('|') {
+            let keys: Option<Vec<_>> = keys.split('+').map(Self::parse_combo_key).collect();
+            let keys = match keys {
+                Some(k) => k,
+                None => continue,
+            }
+            .into_boxed_slice();
+            key_combo.push(keys);
+        }
+
+        key_combo.into_boxed_slice()
+    }
+
+    fn parse_modifier(map: &ConfigMap, name: &str) -> Result<Modifier, String> {
+        let keys = map
+            .get("modifiers")
+            .ok_or("Expected [modifiers] section".to_string())?
+            .get(name)
+            .ok_or(format!("Expected '{name}' key"))?;
+
+        // an empty value disables the modifier
+        Ok(Modifier::new(match keys {
+            Some(keys) if !keys.trim().is_empty() => Self::parse_combos(keys),
+            _ => Box::new([]),
+        }))
+    }
+
+    fn parse_disable_snap(map: &ConfigMap) -> Result<Modifier, String> {
+        Self::parse_modifier(map, "disable_snap")
+    }
+
+    fn parse_angle_snap(map: &ConfigMap) -> Result<Modifier, String> {
+        Self::parse_modifier(map, "angle_snap")
+    }
+
+    fn parse_draw_rect(map: &ConfigMap) -> Result<Modifier, String> {
+        Self::parse_modifier(map, "draw_rect")
+    }
+
+    fn parse_draw_arrow(map: &ConfigMap) -> Result<Modifier, String> {
+        Self::parse_modifier(map, "draw_arrow")
+    }
+
+    fn parse_select(map: &ConfigMap) -> Result<Modifier, String> {
+        Self::parse_modifier(map, "select")
+    }
+
+    fn parse_erase(map: &ConfigMap) -> Result<Modifier, String> {
+        Self::parse_modifier(map, "erase")
+    }
+
+    fn parse_move_canvas(map: &ConfigMap) -> Result<Modifier, String> {
+        Self::parse_modifier(map, "move_canvas")
+    }
+
+    fn parse_scroll_thickness(map: &ConfigMap) -> Result<Modifier, String> {
+        Self::parse_modifier(map, "scroll_thickness")
+    }
+
+    fn parse_select_image(map: &ConfigMap) -> Result<Modifier, String> {
+        Self::parse_modifier(map, "select_image")
+    }
+
     fn parse_action(s: &str) -> Option<Action> {
         match s.trim().to_lowercase().as_str() {
             "undo" => Some(Action::Undo),
@@ -245,10 +1114,56 @@ impl Config {
             "remove" | "remove_image" | "delete" | "delete_image" => Some(Action::Remove),
             "up" | "up_layer" | "move_up_layer" => Some(Action::UpLayer),
             "down" | "down_layer" | "move_down_layer" => Some(Action::DownLayer),
-            a => {
-                eprintln!("[CONFIG] Unknown action '{a}'");
-                None
-            }
+            "note" | "add_note" => Some(Action::AddNote),
+            "remove_note" | "delete_note" => Some(Action::RemoveNote),
+            "lasso" => Some(Action::Lasso),
+            "copy" => Some(Action::Copy),
+            "duplicate" | "duplicate_image" => Some(Action::Duplicate),
+            "copy_image" | "copy_as_image" => Some(Action::CopyImage),
+            "cut" => Some(Action::Cut),
+            "stats" | "canvas_stats" => Some(Action::Stats),
+            "history" => Some(Action::History),
+            "playback" => Some(Action::Playback),
+            "extract_palette" => Some(Action::ExtractPalette),
+            "trace" => Some(Action::Trace),
+            "toggle_tracing" => Some(Action::ToggleTracing),
+            "highlighter" | "toggle_highlighter" => Some(Action::ToggleHighlighter),
+            "nudge_left" => Some(Action::Nudge(-1, 0)),
+            "nudge_right" => Some(Action::Nudge(1, 0)),
+            "nudge_up" => Some(Action::Nudge(0, -1)),
+            "nudge_down" => Some(Action::Nudge(0, 1)),
+            "save" => Some(Action::Save),
+            "open_image" | "open" => Some(Action::OpenImage),
+            "text" | "add_text" => Some(Action::AddText),
+            "export" => Some(Action::Export(ExportArea::View)),
+            "export_all" => Some(Action::Export(ExportArea::Everything)),
+            "export_svg" => Some(Action::ExportSvg),
+            "save_text" => Some(Action::SaveText),
+            "toggle_topmost" | "always_on_top" => Some(Action::ToggleTopmost),
+            "checkpoint" | "add_checkpoint" => Some(Action::Checkpoint),
+            "restore_checkpoint" => Some(Action::RestoreCheckpoint),
+            "clear" | "clear_all" => Some(Action::Clear),
+            "reload_config" | "reload" => Some(Action::ReloadConfig),
+            "reset_view" | "home" => Some(Action::ResetView),
+            "load_text" => Some(Action::LoadText),
+            a => match a.strip_prefix("select_color").map(|n| n.parse::<u8>()) {
+                Some(Ok(n)) if n <= 9 => Some(Action::SelectColor(n)),
+                _ => {
+                    eprintln!("[CONFIG] Unknown action '{a}'");
+                    None
+                }
+            },
+        }
+    }
+
+    fn parse_combo_key(s: &str) -> Option<Key> {
+        match s.trim().to_lowercase().as_str() {
+            "mouse_left" => Some(Key::Mouse(MouseButton::MOUSE_BUTTON_LEFT)),
+            "mouse_right" => Some(Key::Mouse(MouseButton::MOUSE_BUTTON_RIGHT)),
+            "mouse_middle" => Some(Key::Mouse(MouseButton::MOUSE_BUTTON_MIDDLE)),
+            "mouse_back" | "mouse_side" => Some(Key::Mouse(MouseButton::MOUSE_BUTTON_SIDE)),
+            "mouse_forward" | "mouse_extra" => Some(Key::Mouse(MouseButton::MOUSE_BUTTON_EXTRA)),
+            _ => Self::parse_key(s).map(Key::Keyboard),
         }
     }
 
@@ -265,6 +1180,31 @@ impl Config {
             "tab" => Some(KeyboardKey::KEY_TAB),
             "del" | "delete" => Some(KeyboardKey::KEY_DELETE),
             "back" | "backspace" => Some(KeyboardKey::KEY_BACKSPACE),
+            "esc" | "escape" => Some(KeyboardKey::KEY_ESCAPE),
+            "enter" | "return" => Some(KeyboardKey::KEY_ENTER),
+            "home" => Some(KeyboardKey::KEY_HOME),
+            "0" => Some(KeyboardKey::KEY_ZERO),
+            "1" => Some(KeyboardKey::KEY_ONE),
+            "2" => Some(KeyboardKey::KEY_TWO),
+            "3" => Some(KeyboardKey::KEY_THREE),
+            "4" => Some(KeyboardKey::KEY_FOUR),
+            "5" => Some(KeyboardKey::KEY_FIVE),
+            "6" => Some(KeyboardKey::KEY_SIX),
+            "7" => Some(KeyboardKey::KEY_SEVEN),
+            "8" => Some(KeyboardKey::KEY_EIGHT),
+            "9" => Some(KeyboardKey::KEY_NINE),
+            "f1" => Some(KeyboardKey::KEY_F1),
+            "f2" => Some(KeyboardKey::KEY_F2),
+            "f3" => Some(KeyboardKey::KEY_F3),
+            "f4" => Some(KeyboardKey::KEY_F4),
+            "f5" => Some(KeyboardKey::KEY_F5),
+            "f6" => Some(KeyboardKey::KEY_F6),
+            "f7" => Some(KeyboardKey::KEY_F7),
+            "f8" => Some(KeyboardKey::KEY_F8),
+            "f9" => Some(KeyboardKey::KEY_F9),
+            "f10" => Some(KeyboardKey::KEY_F10),
+            "f11" => Some(KeyboardKey::KEY_F11),
+            "f12" => Some(KeyboardKey::KEY_F12),
             "a" => Some(KeyboardKey::KEY_A),
             "b" => Some(KeyboardKey::KEY_B),
             "c" => Some(KeyboardKey::KEY_C),
@@ -298,3 +1238,41 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(path: Option<&str>, env: Option<&str>, dir: Option<&str>) -> Option<ConfigSource> {
+        Config::source(
+            path.map(String::from),
+            env.map(String::from),
+            dir.map(PathBuf::from),
+        )
+    }
+
+    #[test]
+    fn explicit_path_beats_env() {
+        assert_eq!(
+            source(Some("arg.conf"), Some("env.conf"), Some("/config")),
+            Some(ConfigSource::Given("arg.conf".to_string()))
+        );
+    }
+
+    #[test]
+    fn env_beats_config_dir() {
+        assert_eq!(
+            source(None, Some("env.conf"), Some("/config")),
+            Some(ConfigSource::Given("env.conf".to_string()))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_config_dir() {
+        assert_eq!(
+            source(None, None, Some("/config")),
+            Some(ConfigSource::Default(PathBuf::from("/config/kajet.conf")))
+        );
+        assert_eq!(source(None, None, None), None);
+    }
+}
@@ -0,0 +1,494 @@
+use std::{fmt::Write, fs};
+
+use raylib::{
+    RaylibThread,
+    color::Color,
+    ffi,
+    prelude::{RaylibDraw, RaylibDrawHandle},
+    texture::{self, RaylibTexture2D},
+};
+use widok::{Bounds, Camera, CanvasBox, ScreenSize, ToScreen};
+
+use crate::{
+    graphics::{self, Contents, Drawable, Image, Line},
+    save,
+    selection::Selection,
+};
+
+/// What part of the canvas gets exported.
+#[derive(Debug, Clone, Copy)]
+pub enum ExportArea {
+    View,
+    // everything on the canvas at the current zoom
+    Everything,
+    // only what's selected, cropped to it
+    Selection,
+}
+
+/// How the canvas looks in exports.
+#[derive(Clone, Copy)]
+pub struct ExportView<'a> {
+    pub camera: &'a Camera,
+    pub background: Color,
+    // leaves the background out
+    pub transparent: bool,
+}
+
+// the part of the screen that ends up in the image
+struct Frame {
+    origin: ffi::Vector2,
+    size: ScreenSize,
+}
+
+// larger exports get scaled down to fit
+const MAX_SIZE: f32 = 8192.0;
+// space around the drawing when exporting everything, in pixels
+const PADDING: f32 = 20.0;
+
+/// Renders the canvas without the interface into a PNG at `path`.
+/// Has to be called between beginning and ending a frame.
+pub fn export_png(
+    d: &mut RaylibDrawHandle,
+    thread: &RaylibThread,
+    contents: &Contents,
+    selection: &Selection,
+    view: &ExportView,
+    area: ExportArea,
+    path: &str,
+) -> Result<(), String> {
+    let screen = ScreenSize::new(d.get_screen_width() as f32, d.get_screen_height() as f32);
+    let frame = match area {
+        ExportArea::View => Frame {
+            origin: ffi::Vector2 { x: 0.0, y: 0.0 },
+            size: screen,
+        },
+        ExportArea::Selection => {
+            let image = render_selection(d, thread, contents, selection, view)?;
+            let bytes = save::png_bytes(&image)?;
+            return fs::write(path, bytes).map_err(|e| format!("Couldn't write {path} ({e})"));
+        }
+        ExportArea::Everything => {
+            let bounds = contents
+                .bounding_box()
+                .ok_or("Nothing to export".to_string())?
+                .to_screen(view.camera)
+                .inflate(PADDING, PADDING);
+            Frame {
+                origin: ffi::Vector2 {
+                    x: bounds.min.x,
+                    y: bounds.min.y,
+                },
+                size: bounds.size(),
+            }
+        }
+    };
+    let image = render(
+        d,
+        thread,
+        contents.drawables(false),
+        contents.visible_tracing(),
+        view,
+        frame,
+    )?;
+    let bytes = save::png_bytes(&image)?;
+    fs::write(path, bytes).map_err(|e| format!("Couldn't write {path} ({e})"))
+}
+
+/// Renders only the selected drawables, cropped to them.
+/// Has to be called between beginning and ending a frame.
+pub fn render_selection(
+    d: &mut RaylibDrawHandle,
+    thread: &RaylibThread,
+    contents: &Contents,
+    selection: &Selection,
+    view: &ExportView,
+) -> Result<texture::Image, String> {
+    let bounds = selection
+        .bounds(contents)
+        .ok_or("Nothing selected".to_string())?
+        .to_screen(view.camera)
+        .inflate(PADDING, PADDING);
+
+    let drawables = selection.drawables(contents);
+
+    render(
+        d,
+        thread,
+        drawables.into_iter(),
+        None,
+        view,
+        Frame {
+            origin: ffi::Vector2 {
+                x: bounds.min.x,
+                y: bounds.min.y,
+            },
+            size: bounds.size(),
+        },
+    )
+}
+
+/// Draws `drawables` into an image of the screen area in `frame`.
+fn render<'a>(
+    d: &mut RaylibDrawHandle,
+    thread: &RaylibThread,
+    drawables: impl Iterator<Item = &'a dyn Drawable>,
+    tracing: Option<&Image>,
+    view: &ExportView,
+    frame: Frame,
+) -> Result<texture::Image, String> {
+    let size = frame.size;
+    let scale = (MAX_SIZE / size.width.max(size.height)).min(1.0);
+    let (width, height) = (
+        (size.width * scale).ceil().max(1.0) as u32,
+        (size.height * scale).ceil().max(1.0) as u32,
+    );
+
+    let target = d
+        .load_render_texture(thread, width, height)
+        .map_err(|e| e.to_string())?;
+    let view = ffi::Camera2D {
+        offset: ffi::Vector2 { x: 0.0, y: 0.0 },
+        target: frame.origin,
+        rotation: 0.0,
+        zoom: scale,
+    };
+    // the drawables only take the frame's draw handle, so the render
+    // texture is switched to underneath it
+    graphics::begin_target(*target, view);
+    if view.transparent {
+        d.clear_background(Color::BLANK);
+    } else {
+        d.clear_background(view.background);
+    }
+    drawables.for_each(|i| match i.erased() {
+        // erasers paint the background which would leave opaque patches
+        Some(area) if view.transparent => {
+            graphics::clear_box(d, view.camera, area);
+            if let Some(tracing) = tracing {
+                tracing.draw_within(d, view.camera, area);
+            }
+        }
+        _ => graphics::draw_over_tracing(i, tracing, d, view.camera),
+    });
+    graphics::end_target();
+
+    let mut image = target.load_image().map_err(|e| e.to_string())?;
+    // render textures are stored upside down
+    image.flip_vertical();
+    Ok(image)
+}
+
+fn svg_color(c: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+}
+
+fn svg_opacity(c: Color) -> f32 {
+    c.a as f32 / 255.0
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The line as path data, with the same curve the line is drawn with.
+fn line_path(line: &Line) -> String {
+    let p = &line.points;
+    let n = p.len();
+    let mut d = format!("M{} {}", p[0].x, p[0].y);
+    if line.segmented {
+        for q in &p[1..] {
+            write!(d, " L{} {}", q.x, q.y).unwrap();
+        }
+        return d;
+    }
+
+    // mirrored ends like when drawing, the last point only gets reached on short lines
+    let mut ctrl = vec![p[0] + (p[0] - p[1])];
+    ctrl.extend_from_slice(p);
+    if n < 4 {
+        ctrl.push(p[n - 1] + (p[n - 1] - p[n - 2]));
+    }
+    // every catmull-rom segment is a cubic bezier
+    for w in ctrl.windows(4) {
+        let c1 = w[1] + (w[2] - w[0]) / 6.0;
+        let c2 = w[2] - (w[3] - w[1]) / 6.0;
+        write!(
+            d,
+            " C{} {} {} {} {} {}",
+            c1.x, c1.y, c2.x, c2.y, w[2].x, w[2].y
+        )
+        .unwrap();
+    }
+    d
+}
+
+/// Wraps `body` in a group with `erased` cut out of it.
+fn masked(body: String, id: usize, bounds: CanvasBox, erased: CanvasBox) -> String {
+    let rect = |b: CanvasBox, fill: &str| {
+        format!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{fill}"/>"#,
+            b.min.x,
+            b.min.y,
+            b.width(),
+            b.height()
+        )
+    };
+    let mask = format!(
+        r#"<mask id="erased{id}" maskUnits="userSpaceOnUse">{}{}</mask>"#,
+        rect(bounds, "white"),
+        rect(erased, "black")
+    );
+    format!("{mask}\n<g mask=\"url(#erased{id})\">\n{body}</g>\n")
+}
+
+/// Writes the canvas as SVG in canvas coordinates, images get embedded.
+/// Only what's in `selection` gets written if there is one. Without a
+/// `background` erasers mask what's under them instead.
+pub fn export_svg(
+    contents: &Contents,
+    selection: Option<&Selection>,
+    background: Option<Color>,
+) -> Result<String, String> {
+    let drawing = match selection {
+        Some(selection) => selection.copy(contents),
+        None => contents.snapshot(),
+    };
+    let bounds = drawing
+        .bounding_box()
+        .ok_or("Nothing to export".to_string())?;
+    let size = bounds.size();
+
+    // everything tagged with its z to keep the drawing order
+    let mut elements = vec![];
+    // erased areas by z when there's no background to paint them with
+    let mut masks = vec![];
+    for line in &drawing.lines {
+        let color = line.brush.color;
+        let width = line.brush.thickness.0;
+        let element = if line.points.len() == 1 {
+            let p = line.points[0];
+            format!(
+                r#"<circle cx="{}" cy="{}" r="{}" fill="{}" fill-opacity="{}"/>"#,
+                p.x,
+                p.y,
+                width / 2.0,
+                svg_color(color),
+                svg_opacity(color) * line.brush.mode.opacity()
+            )
+        } else {
+            format!(
+                r#"<path d="{}" fill="none" stroke="{}" stroke-opacity="{}" stroke-width="{width}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+                line_path(line),
+                svg_color(color),
+                svg_opacity(color) * line.brush.mode.opacity()
+            )
+        };
+        elements.push((line.z(), element));
+    }
+    for eraser in &drawing.erasers {
+        let b = eraser.bounds();
+        if background.is_none() {
+            masks.push((eraser.z(), b));
+            continue;
+        }
+        elements.push((
+            eraser.z(),
+            format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                b.min.x,
+                b.min.y,
+                b.width(),
+                b.height(),
+                svg_color(eraser.color())
+            ),
+        ));
+    }
+    for rect in &drawing.rects {
+        let (r, color) = (rect.rect, rect.brush.color);
+        elements.push((
+            rect.z(),
+            format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="{}" stroke-opacity="{}" stroke-width="{}"/>"#,
+                r.min.x,
+                r.min.y,
+                r.width(),
+                r.height(),
+                svg_color(color),
+                svg_opacity(color),
+                rect.brush.thickness.0
+            ),
+        ));
+    }
+    for ellipse in &drawing.ellipses {
+        let (c, r, color) = (ellipse.center(), ellipse.radii(), ellipse.brush.color);
+        elements.push((
+            ellipse.z(),
+            format!(
+                r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" fill="none" stroke="{}" stroke-opacity="{}" stroke-width="{}"/>"#,
+                c.x,
+                c.y,
+                r.x,
+                r.y,
+                svg_color(color),
+                svg_opacity(color),
+                ellipse.brush.thickness.0
+            ),
+        ));
+    }
+    for arrow in &drawing.arrows {
+        let color = arrow.brush.color;
+        let [tip, left, right] = arrow.head();
+        let base = left.lerp(right, 0.5);
+        elements.push((
+            arrow.z(),
+            format!(
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-opacity="{}" stroke-width="{}"/><polygon points="{},{} {},{} {},{}" fill="{}" fill-opacity="{}"/>"#,
+                arrow.start.x,
+                arrow.start.y,
+                base.x,
+                base.y,
+                svg_color(color),
+                svg_opacity(color),
+                arrow.brush.thickness.0,
+                tip.x,
+                tip.y,
+                left.x,
+                left.y,
+                right.x,
+                right.y,
+                svg_color(color),
+                svg_opacity(color)
+            ),
+        ));
+    }
+    for text in &drawing.texts {
+        elements.push((
+            text.z(),
+            format!(
+                r#"<text x="{}" y="{}" font-size="{}" font-family="sans-serif" dominant-baseline="hanging" fill="{}" fill-opacity="{}">{}</text>"#,
+                text.pos.x,
+                text.pos.y,
+                text.size.0,
+                svg_color(text.color),
+                svg_opacity(text.color),
+                escape_xml(&text.text)
+            ),
+        ));
+    }
+    for image in &drawing.images {
+        let pixels = image.texture.load_image().map_err(|e| e.to_string())?;
+        let data = save::base64_encode(&save::png_bytes(&pixels)?);
+        elements.push((
+            image.z,
+            format!(
+                r#"<image x="{}" y="{}" width="{}" height="{}" href="data:image/png;base64,{data}"/>"#,
+                image.pos.x,
+                image.pos.y,
+                image.width().0,
+                image.height().0
+            ),
+        ));
+    }
+    elements.sort_by_key(|(z, _)| *z);
+    masks.sort_by_key(|(z, _)| *z);
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        bounds.min.x, bounds.min.y, size.width, size.height, size.width, size.height
+    );
+    svg.push('\n');
+    if let Some(background) = background {
+        writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            bounds.min.x,
+            bounds.min.y,
+            size.width,
+            size.height,
+            svg_color(background)
+        )
+        .unwrap();
+    }
+
+    let mut body = String::new();
+    let mut masks = masks.into_iter().enumerate().peekable();
+    for (z, element) in elements {
+        // an eraser hides everything drawn before it
+        while let Some((i, (_, erased))) = masks.next_if(|(_, (mz, _))| *mz < z) {
+            body = masked(body, i, bounds, erased);
+        }
+        body.push_str(&element);
+        body.push('\n');
+    }
+    for (i, (_, erased)) in masks {
+        body = masked(body, i, bounds, erased);
+    }
+    svg.push_str(&body);
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use widok::{CanvasLength, CanvasPoint, CanvasRect, CanvasSize};
+
+    use super::*;
+    use crate::graphics::{Brush, BrushMode, Eraser};
+
+    fn contents() -> Contents {
+        let mut contents = Contents::new();
+        let brush = Brush {
+            color: Color::RED,
+            thickness: CanvasLength::new(2.0),
+            mode: BrushMode::Pen,
+        };
+        let id = contents.next_line_id();
+        let mut line = Line::new(CanvasPoint::new(0.0, 0.0), brush, id, 0);
+        line.points.push(CanvasPoint::new(10.0, 10.0));
+        contents.lines.push(line);
+        let id = contents.next_eraser_id();
+        let rect = CanvasRect::new(CanvasPoint::new(2.0, 2.0), CanvasSize::new(4.0, 4.0));
+        contents
+            .erasers
+            .push(Eraser::new(rect, Color::WHITE, id, 1));
+        contents
+    }
+
+    #[test]
+    fn svg_erasers_paint_the_background() {
+        let svg = export_svg(&contents(), None, Some(Color::BLACK)).unwrap();
+        assert!(svg.contains(r##"fill="#ffffff""##));
+        assert!(!svg.contains("<mask"));
+    }
+
+    #[test]
+    fn transparent_svg_erasers_mask_what_is_under_them() {
+        let svg = export_svg(&contents(), None, None).unwrap();
+        assert!(!svg.contains(r##"fill="#ffffff""##));
+        let (mask, group) = svg.split_once("<g mask=").unwrap();
+        assert!(mask.contains(r#"<rect x="2" y="2" width="4" height="4" fill="black"/>"#));
+        assert!(group.contains("<path"));
+    }
+
+    #[test]
+    fn svg_of_a_selection_leaves_the_rest_out() {
+        let mut contents = contents();
+        let selected = contents.lines[0].id;
+        let brush = contents.lines[0].brush;
+        let id = contents.next_line_id();
+        let mut line = Line::new(CanvasPoint::new(40.0, 40.0), brush, id, 2);
+        line.points.push(CanvasPoint::new(50.0, 50.0));
+        contents.lines.push(line);
+
+        let selection = Selection {
+            lines: vec![selected],
+            ..Default::default()
+        };
+        let svg = export_svg(&contents, Some(&selection), Some(Color::BLACK)).unwrap();
+        assert_eq!(svg.matches("<path").count(), 1);
+        assert!(!svg.contains(r##"fill="#ffffff""##));
+        assert!(svg.contains(r#"viewBox="-2 -2 14 14""#));
+    }
+}
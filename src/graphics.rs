@@ -1,27 +1,136 @@
-use std::rc::Rc;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    f32::consts::TAU,
+    ffi::CString,
+    mem,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use raylib::{
+    RaylibHandle, RaylibThread,
     color::Color,
+    ffi,
     math::Vector2,
     prelude::{RaylibDraw, RaylibDrawHandle},
-    texture::Texture2D,
+    texture::{RaylibTexture2D, Texture2D},
 };
 use widok::{
-    Bounds, Camera, CanvasBox, CanvasPoint, CanvasRect, CanvasSpace, CanvasVector, InView, Length,
-    Rect, ScreenPoint, ScreenSize, ScreenSpace, ToScreen,
+    Bounds, Camera, CanvasBox, CanvasLength, CanvasPoint, CanvasRect, CanvasSize, CanvasSpace,
+    CanvasVector, InView, Length, Rect, ScreenPoint, ScreenSize, ScreenSpace, ToCanvas, ToScreen,
 };
 
-use crate::config::Config;
+use crate::config::{Config, Quality};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ImageId(usize);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LineId(usize);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EraserId(usize);
+/// Shared by rectangles, arrows and text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapeId(usize);
+/// The next id of every kind at some point, anything with a later id was
+/// added after it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdMark {
+    line: usize,
+    image: usize,
+    eraser: usize,
+    shape: usize,
+}
+
+/// The box enclosing all of `items`.
+pub fn bounding_box<'a>(items: impl Iterator<Item = &'a dyn Bounds>) -> Option<CanvasBox> {
+    items.map(|i| i.bounds()).reduce(|a, b| a.union(&b))
+}
+
+/// The drawing at some point in time, images share their textures.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub lines: Vec<Line>,
+    pub images: Vec<Image>,
+    pub erasers: Vec<Eraser>,
+    pub rects: Vec<Rectangle>,
+    pub ellipses: Vec<Ellipse>,
+    pub arrows: Vec<Arrow>,
+    pub texts: Vec<Text>,
+    pub z: usize,
+}
+
+impl Snapshot {
+    /// The box enclosing everything in the snapshot.
+    pub fn bounding_box(&self) -> Option<CanvasBox> {
+        bounding_box(
+            self.lines
+                .iter()
+                .map(|l| l as &dyn Bounds)
+                .chain(self.images.iter().map(|i| i as &dyn Bounds))
+                .chain(self.erasers.iter().map(|e| e as &dyn Bounds))
+                .chain(self.rects.iter().map(|r| r as &dyn Bounds))
+                .chain(self.ellipses.iter().map(|e| e as &dyn Bounds))
+                .chain(self.arrows.iter().map(|a| a as &dyn Bounds))
+                .chain(self.texts.iter().map(|t| t as &dyn Bounds)),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+            && self.images.is_empty()
+            && self.erasers.is_empty()
+            && self.rects.is_empty()
+            && self.ellipses.is_empty()
+            && self.arrows.is_empty()
+            && self.texts.is_empty()
+    }
+
+    /// Drops everything that isn't in `contents` anymore.
+    pub fn retain_present(&mut self, contents: &Contents) {
+        let lines = contents.lines.iter().map(|l| l.id).collect::<HashSet<_>>();
+        let images = contents.images.iter().map(|i| i.id).collect::<HashSet<_>>();
+        let erasers = contents
+            .erasers
+            .iter()
+            .map(|e| e.id)
+            .collect::<HashSet<_>>();
+        let shapes = contents
+            .rects
+            .iter()
+            .map(|r| r.id)
+            .chain(contents.ellipses.iter().map(|e| e.id))
+            .chain(contents.arrows.iter().map(|a| a.id))
+            .chain(contents.texts.iter().map(|t| t.id))
+            .collect::<HashSet<_>>();
+
+        self.lines.retain(|l| lines.contains(&l.id));
+        self.images.retain(|i| images.contains(&i.id));
+        self.erasers.retain(|e| erasers.contains(&e.id));
+        self.rects.retain(|r| shapes.contains(&r.id));
+        self.ellipses.retain(|e| shapes.contains(&e.id));
+        self.arrows.retain(|a| shapes.contains(&a.id));
+        self.texts.retain(|t| shapes.contains(&t.id));
+    }
+}
+
 pub struct Contents {
     pub overlay: Vec<Box<dyn Drawable>>,
     pub lines: Vec<Line>,
     pub images: Vec<Image>,
     pub erasers: Vec<Eraser>,
+    pub rects: Vec<Rectangle>,
+    pub ellipses: Vec<Ellipse>,
+    pub arrows: Vec<Arrow>,
+    pub texts: Vec<Text>,
+    // a reference drawn under everything else that can't be selected or changed
+    pub tracing: Option<Image>,
+    pub show_tracing: bool,
     pub z: usize,
     next_image_id: ImageId,
+    next_line_id: LineId,
+    next_eraser_id: EraserId,
+    next_shape_id: ShapeId,
 }
 
 impl Contents {
@@ -31,8 +140,17 @@ impl Contents {
             lines: vec![],
             images: vec![],
             erasers: vec![],
+            rects: vec![],
+            ellipses: vec![],
+            arrows: vec![],
+            texts: vec![],
+            tracing: None,
+            show_tracing: true,
             z: 0,
             next_image_id: ImageId(0),
+            next_line_id: LineId(0),
+            next_eraser_id: EraserId(0),
+            next_shape_id: ShapeId(0),
         }
     }
 
@@ -41,10 +159,176 @@ impl Contents {
         ImageId(self.next_image_id.0 - 1)
     }
 
+    pub fn next_line_id(&mut self) -> LineId {
+        self.next_line_id.0 += 1;
+        LineId(self.next_line_id.0 - 1)
+    }
+
+    pub fn next_eraser_id(&mut self) -> EraserId {
+        self.next_eraser_id.0 += 1;
+        EraserId(self.next_eraser_id.0 - 1)
+    }
+
+    pub fn next_shape_id(&mut self) -> ShapeId {
+        self.next_shape_id.0 += 1;
+        ShapeId(self.next_shape_id.0 - 1)
+    }
+
+    pub fn id_mark(&self) -> IdMark {
+        IdMark {
+            line: self.next_line_id.0,
+            image: self.next_image_id.0,
+            eraser: self.next_eraser_id.0,
+            shape: self.next_shape_id.0,
+        }
+    }
+
+    /// Copies of everything added after `mark` was taken.
+    pub fn added_since(&self, mark: IdMark) -> Snapshot {
+        Snapshot {
+            lines: self
+                .lines
+                .iter()
+                .filter(|l| l.id.0 >= mark.line)
+                .cloned()
+                .collect(),
+            images: self
+                .images
+                .iter()
+                .filter(|i| i.id.0 >= mark.image)
+                .cloned()
+                .collect(),
+            erasers: self
+                .erasers
+                .iter()
+                .filter(|e| e.id.0 >= mark.eraser)
+                .cloned()
+                .collect(),
+            rects: self
+                .rects
+                .iter()
+                .filter(|r| r.id.0 >= mark.shape)
+                .cloned()
+                .collect(),
+            ellipses: self
+                .ellipses
+                .iter()
+                .filter(|e| e.id.0 >= mark.shape)
+                .cloned()
+                .collect(),
+            arrows: self
+                .arrows
+                .iter()
+                .filter(|a| a.id.0 >= mark.shape)
+                .cloned()
+                .collect(),
+            texts: self
+                .texts
+                .iter()
+                .filter(|t| t.id.0 >= mark.shape)
+                .cloned()
+                .collect(),
+            z: self.z,
+        }
+    }
+
+    /// Adds everything in `snapshot` to the drawing.
+    pub fn add(&mut self, snapshot: Snapshot) {
+        self.lines.extend(snapshot.lines);
+        self.images.extend(snapshot.images);
+        self.erasers.extend(snapshot.erasers);
+        self.rects.extend(snapshot.rects);
+        self.ellipses.extend(snapshot.ellipses);
+        self.arrows.extend(snapshot.arrows);
+        self.texts.extend(snapshot.texts);
+        self.z = self.z.max(snapshot.z);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            lines: self.lines.clone(),
+            images: self.images.clone(),
+            erasers: self.erasers.clone(),
+            rects: self.rects.clone(),
+            ellipses: self.ellipses.clone(),
+            arrows: self.arrows.clone(),
+            texts: self.texts.clone(),
+            z: self.z,
+        }
+    }
+
+    /// Puts `snapshot` in place of the drawing and returns what was there.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Snapshot {
+        Snapshot {
+            lines: mem::replace(&mut self.lines, snapshot.lines),
+            images: mem::replace(&mut self.images, snapshot.images),
+            erasers: mem::replace(&mut self.erasers, snapshot.erasers),
+            rects: mem::replace(&mut self.rects, snapshot.rects),
+            ellipses: mem::replace(&mut self.ellipses, snapshot.ellipses),
+            arrows: mem::replace(&mut self.arrows, snapshot.arrows),
+            texts: mem::replace(&mut self.texts, snapshot.texts),
+            z: mem::replace(&mut self.z, snapshot.z),
+        }
+    }
+
+    /// Lines, images, erasers, shapes and text sorted by z, followed by the overlay if
+    /// `overlay` is set.
+    pub fn drawables(&self, overlay: bool) -> impl Iterator<Item = &dyn Drawable> {
+        let mut sorted = self.unsorted_drawables().collect::<Vec<_>>();
+        sorted.sort_by_key(|i| i.z());
+
+        let overlay: &[Box<dyn Drawable>] = if overlay { &self.overlay } else { &[] };
+        self.visible_tracing()
+            .map(|i| i as &dyn Drawable)
+            .into_iter()
+            .chain(sorted)
+            .chain(overlay.iter().map(|i| &**i as &dyn Drawable))
+    }
+
+    /// Lines, images, erasers, shapes and text without sorting them.
+    pub fn unsorted_drawables(&self) -> impl Iterator<Item = &dyn Drawable> {
+        self.images
+            .iter()
+            .map(|i| i as &dyn Drawable)
+            .chain(self.lines.iter().map(|i| i as &dyn Drawable))
+            .chain(self.erasers.iter().map(|i| i as &dyn Drawable))
+            .chain(self.rects.iter().map(|i| i as &dyn Drawable))
+            .chain(self.ellipses.iter().map(|i| i as &dyn Drawable))
+            .chain(self.arrows.iter().map(|i| i as &dyn Drawable))
+            .chain(self.texts.iter().map(|i| i as &dyn Drawable))
+    }
+
+    pub fn visible_tracing(&self) -> Option<&Image> {
+        self.tracing.as_ref().filter(|_| self.show_tracing)
+    }
+
+    /// The box enclosing everything on the canvas.
+    pub fn bounding_box(&self) -> Option<CanvasBox> {
+        bounding_box(
+            self.lines
+                .iter()
+                .map(|l| l as &dyn Bounds)
+                .chain(self.images.iter().map(|i| i as &dyn Bounds))
+                .chain(self.erasers.iter().map(|e| e as &dyn Bounds))
+                .chain(self.rects.iter().map(|r| r as &dyn Bounds))
+                .chain(self.ellipses.iter().map(|e| e as &dyn Bounds))
+                .chain(self.arrows.iter().map(|a| a as &dyn Bounds))
+                .chain(self.texts.iter().map(|t| t as &dyn Bounds)),
+        )
+    }
+
+    pub fn line(&mut self, id: LineId) -> Option<&mut Line> {
+        self.lines.iter_mut().find(|l| l.id == id)
+    }
+
     pub fn image(&mut self, id: ImageId) -> Option<&mut Image> {
         self.images.iter_mut().find(|i| i.id == id)
     }
 
+    pub fn eraser(&mut self, id: EraserId) -> Option<&mut Eraser> {
+        self.erasers.iter_mut().find(|e| e.id == id)
+    }
+
     pub fn remove_image(&mut self, id: ImageId) -> Option<Image> {
         let index = self.images.iter().position(|i| i.id == id)?;
 
@@ -64,23 +348,119 @@ impl Contents {
             img.z = img.z.saturating_sub(1);
         }
     }
+
+    pub fn move_image_to_front(&mut self, id: ImageId) {
+        let max_z = self.z;
+
+        if let Some(img) = self.image(id) {
+            img.z = max_z;
+        }
+    }
+
+    pub fn move_image_to_back(&mut self, id: ImageId) {
+        if let Some(img) = self.image(id) {
+            img.z = 0;
+        }
+    }
+
+    /// Finds the smallest offset (within `tolerance`) that aligns an edge or
+    /// the center of `bounds` with an edge or center of another image.
+    pub fn align_image(
+        &self,
+        id: ImageId,
+        bounds: CanvasBox,
+        tolerance: f32,
+    ) -> (CanvasVector, Vec<Guide>) {
+        fn anchors(min: f32, max: f32) -> [f32; 3] {
+            [min, (min + max) / 2.0, max]
+        }
+
+        fn snap(moving: [f32; 3], targets: &[[f32; 3]], tolerance: f32) -> Option<(f32, f32)> {
+            targets
+                .iter()
+                .flatten()
+                .flat_map(|&t| moving.iter().map(move |&m| (t - m, t)))
+                .filter(|(offset, _)| offset.abs() <= tolerance)
+                .min_by(|a, b| a.0.abs().total_cmp(&b.0.abs()))
+        }
+
+        let others = self
+            .images
+            .iter()
+            .filter(|i| i.id != id)
+            .map(|i| i.bounds())
+            .collect::<Vec<_>>();
+        let xs = others
+            .iter()
+            .map(|b| anchors(b.min.x, b.max.x))
+            .collect::<Vec<_>>();
+        let ys = others
+            .iter()
+            .map(|b| anchors(b.min.y, b.max.y))
+            .collect::<Vec<_>>();
+
+        let mut offset = CanvasVector::zero();
+        let mut guides = vec![];
+        if let Some((dx, x)) = snap(anchors(bounds.min.x, bounds.max.x), &xs, tolerance) {
+            offset.x = dx;
+            guides.push(Guide::Vertical(x));
+        }
+        if let Some((dy, y)) = snap(anchors(bounds.min.y, bounds.max.y), &ys, tolerance) {
+            offset.y = dy;
+            guides.push(Guide::Horizontal(y));
+        }
+
+        (offset, guides)
+    }
+}
+
+/// An alignment line at a canvas coordinate.
+#[derive(Debug, Clone, Copy)]
+pub enum Guide {
+    Vertical(f32),
+    Horizontal(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushMode {
+    Pen,
+    // translucent, without getting darker where the stroke overlaps itself
+    Highlighter,
+}
+
+impl BrushMode {
+    const HIGHLIGHTER_OPACITY: f32 = 0.4;
+
+    /// How much of the color shows through.
+    pub fn opacity(self) -> f32 {
+        match self {
+            Self::Pen => 1.0,
+            Self::Highlighter => Self::HIGHLIGHTER_OPACITY,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct Brush<Space> {
     pub color: Color,
     pub thickness: Length<Space>,
+    pub mode: BrushMode,
 }
 
 pub trait Drawable: InView {
     fn z(&self) -> usize;
     fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera);
+    /// The area painted over with the background, if any.
+    fn erased(&self) -> Option<CanvasBox> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct FilledCircle {
     pub pos: ScreenPoint,
-    pub brush: Brush<ScreenSpace>,
+    // converted when drawn so the radius follows the same camera as the canvas
+    pub brush: Brush<CanvasSpace>,
 }
 
 impl InView for FilledCircle {
@@ -117,14 +497,55 @@ impl Drawable for FilledCircle {
         0
     }
 
-    fn draw(&self, d: &mut RaylibDrawHandle, _: &Camera) {
-        let r = self.brush.thickness / 2.0;
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let r = self.brush.thickness.to_screen(camera) / 2.0;
         let r = r.0.max(1.0);
 
         d.draw_circle_v(self.pos.into_vec2(), r, self.brush.color);
     }
 }
 
+/// A ring expanding from the cursor and fading out.
+#[derive(Debug, Clone, Copy)]
+pub struct Pulse {
+    pub pos: ScreenPoint,
+    pub brush: Brush<CanvasSpace>,
+    // from 0 to 1 over the animation
+    pub progress: f32,
+}
+
+impl Pulse {
+    const GROWTH: f32 = 12.0;
+}
+
+impl InView for Pulse {
+    fn is_in_view(&self, _camera: &Camera) -> bool {
+        // always visible since it's in ScreenSpace
+        true
+    }
+}
+
+impl Drawable for Pulse {
+    fn z(&self) -> usize {
+        0
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let r = (self.brush.thickness.to_screen(camera) / 2.0).0.max(1.0);
+        let r = r + 2.0 + Self::GROWTH * self.progress;
+
+        d.draw_ring(
+            self.pos.into_vec2(),
+            r,
+            r + 2.0,
+            0.0,
+            360.0,
+            32,
+            self.brush.color.alpha(1.0 - self.progress),
+        );
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct FilledRect {
     pub rect: CanvasRect,
@@ -170,163 +591,860 @@ impl Drawable for FilledRect {
     }
 }
 
-#[derive(Debug)]
-pub struct StraightLine {
-    pub start: ScreenPoint,
-    pub end: ScreenPoint,
-    pub brush: Brush<ScreenSpace>,
+/// An outlined rectangle drawn with the rectangle tool.
+#[derive(Debug, Clone, Copy)]
+pub struct Rectangle {
+    // the outline runs along the edges of this box
+    pub rect: CanvasBox,
+    pub brush: Brush<CanvasSpace>,
+    pub id: ShapeId,
+    z: usize,
 }
 
-impl InView for StraightLine {
-    fn is_in_view(&self, _camera: &Camera) -> bool {
-        // always visible since it's in ScreenSpace
-        true
+impl Rectangle {
+    pub fn new(
+        a: CanvasPoint,
+        b: CanvasPoint,
+        brush: Brush<CanvasSpace>,
+        id: ShapeId,
+        z: usize,
+    ) -> Self {
+        Self {
+            rect: CanvasBox::from_points([a, b]),
+            brush,
+            id,
+            z,
+        }
+    }
+
+    pub fn copy_as(&self, id: ShapeId, z: usize) -> Self {
+        Self { id, z, ..*self }
+    }
+
+    pub fn translate(&mut self, offset: CanvasVector) {
+        self.rect = self.rect.translate(offset);
     }
 }
 
-impl Drawable for StraightLine {
+impl Bounds for Rectangle {
+    fn bounds(&self) -> CanvasBox {
+        let half = self.brush.thickness.0 / 2.0;
+        self.rect.inflate(half, half)
+    }
+}
+
+impl Drawable for Rectangle {
     fn z(&self) -> usize {
-        0
+        self.z
     }
 
-    fn draw(&self, d: &mut RaylibDrawHandle, _camera: &Camera) {
-        d.draw_line_ex(
-            self.start.into_vec2(),
-            self.end.into_vec2(),
-            self.brush.thickness.0,
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        // raylib draws the outline inside the given rectangle
+        let outer = self.bounds().to_screen(camera);
+        let size = outer.size();
+        d.draw_rectangle_lines_ex(
+            raylib::math::Rectangle::new(outer.min.x, outer.min.y, size.width, size.height),
+            self.brush.thickness.to_screen(camera).0,
             self.brush.color,
         );
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Line {
-    pub points: Vec<CanvasPoint>,
-    pub finished: bool,
+/// An outlined ellipse, what strokes recognized as circles become.
+#[derive(Debug, Clone, Copy)]
+pub struct Ellipse {
+    // the outline touches the edges of this box
+    pub rect: CanvasBox,
     pub brush: Brush<CanvasSpace>,
+    pub id: ShapeId,
     z: usize,
 }
 
-impl Line {
-    pub fn new(start: CanvasPoint, brush: Brush<CanvasSpace>, z: usize) -> Self {
-        Self {
-            points: vec![start],
-            finished: false,
-            brush,
-            z,
-        }
+impl Ellipse {
+    const SEGMENTS: usize = 64;
+
+    pub fn new(rect: CanvasBox, brush: Brush<CanvasSpace>, id: ShapeId, z: usize) -> Self {
+        Self { rect, brush, id, z }
     }
 
-    fn draw_longer(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
-        assert!(self.points.len() >= 4);
-
-        let first = (self.points[0] * 2.0 - self.points[1]).to_point();
-        let pts = std::iter::once(&first)
-            .chain(self.points.iter())
-            .map(|p| Vector2::from(p.to_screen(camera).into_vec2()))
-            .collect::<Box<_>>();
-        d.draw_spline_catmull_rom(
-            &pts,
-            self.brush.thickness.to_screen(camera).0,
-            self.brush.color,
-        );
+    pub fn copy_as(&self, id: ShapeId, z: usize) -> Self {
+        Self { id, z, ..*self }
     }
 
-    fn draw_shorter(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
-        let r = self.brush.thickness.to_screen(camera) / 2.0;
-        for seg in self.points.windows(2) {
-            let p0 = seg[0].to_screen(camera);
-            let p1 = seg[1].to_screen(camera);
-            d.draw_circle_v(p0.into_vec2(), r.0, self.brush.color);
-            d.draw_circle_v(p1.into_vec2(), r.0, self.brush.color);
-            d.draw_line_ex(
-                p0.into_vec2(),
-                p1.into_vec2(),
-                self.brush.thickness.to_screen(camera).0,
-                self.brush.color,
-            );
-        }
+    pub fn translate(&mut self, offset: CanvasVector) {
+        self.rect = self.rect.translate(offset);
     }
 
-    fn draw_single(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
-        assert!(self.points.len() == 1);
+    pub fn center(&self) -> CanvasPoint {
+        self.rect.center()
+    }
 
-        let p = self.points[0].to_screen(camera);
-        let r = self.brush.thickness.to_screen(camera) / 2.0;
+    /// Half the width and height.
+    pub fn radii(&self) -> CanvasVector {
+        self.rect.size().to_vector() / 2.0
+    }
 
-        d.draw_circle_v(p.into_vec2(), r.0, self.brush.color);
+    /// Points along the outline, the first one is repeated at the end.
+    fn outline(&self) -> impl Iterator<Item = CanvasPoint> {
+        let (center, radii) = (self.center(), self.radii());
+        (0..=Self::SEGMENTS).map(move |i| {
+            let angle = i as f32 / Self::SEGMENTS as f32 * TAU;
+            center + CanvasVector::new(angle.cos() * radii.x, angle.sin() * radii.y)
+        })
     }
 }
 
-impl Bounds for Line {
+impl Bounds for Ellipse {
     fn bounds(&self) -> CanvasBox {
-        debug_assert!(!self.points.is_empty());
-
-        let r = self.brush.thickness.0;
-
-        let min_x = self
-            .points
-            .iter()
-            .map(|a| a.x)
-            .min_by(f32::total_cmp)
-            .unwrap()
-            - r;
-        let min_y = self
-            .points
-            .iter()
-            .map(|a| a.y)
-            .min_by(f32::total_cmp)
-            .unwrap()
-            - r;
-        let max_x = self
-            .points
-            .iter()
-            .map(|a| a.x)
-            .max_by(f32::total_cmp)
-            .unwrap()
-            + r;
-        let max_y = self
-            .points
-            .iter()
-            .map(|a| a.y)
-            .max_by(f32::total_cmp)
-            .unwrap()
-            + r;
-
-        CanvasBox::new(
-            CanvasPoint::new(min_x, min_y),
-            CanvasPoint::new(max_x, max_y),
-        )
+        let half = self.brush.thickness.0 / 2.0;
+        self.rect.inflate(half, half)
     }
 }
 
-impl Drawable for Line {
+impl Drawable for Ellipse {
     fn z(&self) -> usize {
         self.z
     }
 
     fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
-        match self.points.len() {
-            0 => {}
-            1 => self.draw_single(d, camera),
-            2..4 => self.draw_shorter(d, camera),
-            4.. => self.draw_longer(d, camera),
-        }
+        let points = self
+            .outline()
+            .map(|p| {
+                let p = p.to_screen(camera);
+                Vector2::new(p.x, p.y)
+            })
+            .collect::<Vec<_>>();
+        d.draw_spline_linear(
+            &points,
+            self.brush.thickness.to_screen(camera).0,
+            self.brush.color,
+        );
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Image {
-    pub pos: CanvasPoint,
-    pub texture: Rc<Texture2D>,
-    pub is_selected: bool,
-    pub scale: Length<CanvasSpace>,
-    pub id: ImageId,
-    pub z: usize,
-    border_color: Color,
+/// A straight line with a triangle head at `end`.
+#[derive(Debug, Clone, Copy)]
+pub struct Arrow {
+    pub start: CanvasPoint,
+    pub end: CanvasPoint,
+    pub brush: Brush<CanvasSpace>,
+    pub id: ShapeId,
+    z: usize,
 }
 
-impl Image {
+impl Arrow {
+    // head size relative to the thickness
+    const HEAD_LENGTH: f32 = 4.0;
+    const HEAD_WIDTH: f32 = 3.0;
+
+    pub fn new(
+        start: CanvasPoint,
+        end: CanvasPoint,
+        brush: Brush<CanvasSpace>,
+        id: ShapeId,
+        z: usize,
+    ) -> Self {
+        Self {
+            start,
+            end,
+            brush,
+            id,
+            z,
+        }
+    }
+
+    pub fn copy_as(&self, id: ShapeId, z: usize) -> Self {
+        Self { id, z, ..*self }
+    }
+
+    pub fn translate(&mut self, offset: CanvasVector) {
+        self.start += offset;
+        self.end += offset;
+    }
+
+    /// The tip and the two back corners of the head.
+    pub fn head(&self) -> [CanvasPoint; 3] {
+        let thickness = self.brush.thickness.0.max(1.0);
+        let along = (self.end - self.start)
+            .try_normalize()
+            .unwrap_or(CanvasVector::new(1.0, 0.0));
+        let across = CanvasVector::new(-along.y, along.x);
+        let base = self.end - along * thickness * Self::HEAD_LENGTH;
+        let half_width = thickness * Self::HEAD_WIDTH / 2.0;
+        [
+            self.end,
+            base + across * half_width,
+            base - across * half_width,
+        ]
+    }
+}
+
+impl Bounds for Arrow {
+    fn bounds(&self) -> CanvasBox {
+        let half = self.brush.thickness.0 / 2.0;
+        CanvasBox::from_points(std::iter::once(self.start).chain(self.head())).inflate(half, half)
+    }
+}
+
+impl Drawable for Arrow {
+    fn z(&self) -> usize {
+        self.z
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let [tip, left, right] = self.head().map(|p| p.to_screen(camera));
+        // the line stops at the head so a translucent arrow doesn't overlap itself
+        StraightLine {
+            start: self.start.to_screen(camera),
+            end: left.lerp(right, 0.5),
+            brush: Brush {
+                color: self.brush.color,
+                thickness: self.brush.thickness.to_screen(camera),
+                mode: BrushMode::Pen,
+            },
+        }
+        .draw(d, camera);
+
+        // raylib only fills triangles given counter-clockwise
+        let (b, c) = if (left - tip).cross(right - tip) < 0.0 {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        d.draw_triangle(
+            tip.into_vec2(),
+            b.into_vec2(),
+            c.into_vec2(),
+            self.brush.color,
+        );
+    }
+}
+
+/// A line of text anchored at its top left corner, sized in canvas units.
+#[derive(Debug, Clone)]
+pub struct Text {
+    pub pos: CanvasPoint,
+    pub text: String,
+    pub size: CanvasLength,
+    pub color: Color,
+    pub id: ShapeId,
+    z: usize,
+}
+
+impl Text {
+    pub fn new(
+        pos: CanvasPoint,
+        text: String,
+        size: CanvasLength,
+        color: Color,
+        id: ShapeId,
+        z: usize,
+    ) -> Self {
+        Self {
+            pos,
+            text,
+            size,
+            color,
+            id,
+            z,
+        }
+    }
+
+    pub fn copy_as(&self, id: ShapeId, z: usize) -> Self {
+        Self {
+            id,
+            z,
+            ..self.clone()
+        }
+    }
+
+    pub fn translate(&mut self, offset: CanvasVector) {
+        self.pos += offset;
+    }
+
+    // same as raylib uses for the default font
+    fn spacing(size: f32) -> f32 {
+        size / 10.0
+    }
+
+    pub fn width(&self) -> CanvasLength {
+        let text = CString::new(self.text.as_str()).unwrap_or_default();
+        let size = unsafe {
+            ffi::MeasureTextEx(
+                ffi::GetFontDefault(),
+                text.as_ptr(),
+                self.size.0,
+                Self::spacing(self.size.0),
+            )
+        };
+        CanvasLength::new(size.x)
+    }
+}
+
+impl Bounds for Text {
+    fn bounds(&self) -> CanvasBox {
+        CanvasRect::new(self.pos, CanvasSize::new(self.width().0, self.size.0)).to_box2d()
+    }
+}
+
+impl Drawable for Text {
+    fn z(&self) -> usize {
+        self.z
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let size = self.size.to_screen(camera).0;
+        // draw_text doesn't go below 10 pixels, so zooming out wouldn't shrink it
+        let font = d.get_font_default();
+        d.draw_text_ex(
+            font,
+            &self.text,
+            self.pos.to_screen(camera).into_vec2(),
+            size,
+            Self::spacing(size),
+            self.color,
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct StraightLine {
+    pub start: ScreenPoint,
+    pub end: ScreenPoint,
+    pub brush: Brush<ScreenSpace>,
+}
+
+impl InView for StraightLine {
+    fn is_in_view(&self, _camera: &Camera) -> bool {
+        // always visible since it's in ScreenSpace
+        true
+    }
+}
+
+impl Drawable for StraightLine {
+    fn z(&self) -> usize {
+        0
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, _camera: &Camera) {
+        // a single segment can't overlap itself, so no need for a layer
+        let color = self.brush.color;
+        d.draw_line_ex(
+            self.start.into_vec2(),
+            self.end.into_vec2(),
+            self.brush.thickness.0,
+            color.alpha(color.a as f32 / 255.0 * self.brush.mode.opacity()),
+        );
+    }
+}
+
+thread_local! {
+    // reused between frames so long strokes don't allocate on every draw
+    static SPLINE_POINTS: RefCell<Vec<Vector2>> = const { RefCell::new(Vec::new()) };
+    // the render texture drawing goes to instead of the window, see `begin_target`
+    static TARGET: Cell<Option<(ffi::RenderTexture2D, ffi::Camera2D)>> = const { Cell::new(None) };
+    // reused between frames by `draw_layered`
+    static LAYER: Cell<Option<ffi::RenderTexture2D>> = const { Cell::new(None) };
+    // how lines get drawn, see `set_quality`
+    static QUALITY: Cell<Quality> = const { Cell::new(Quality::Medium) };
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub points: Vec<CanvasPoint>,
+    pub finished: bool,
+    pub brush: Brush<CanvasSpace>,
+    // drawn as straight segments instead of a spline
+    pub segmented: bool,
+    pub id: LineId,
+    z: usize,
+}
+
+impl Line {
+    pub fn new(start: CanvasPoint, brush: Brush<CanvasSpace>, id: LineId, z: usize) -> Self {
+        Self {
+            points: vec![start],
+            finished: false,
+            brush,
+            segmented: false,
+            id,
+            z,
+        }
+    }
+
+    /// A copy of the line with a new identity.
+    pub fn copy_as(&self, id: LineId, z: usize) -> Self {
+        Self {
+            id,
+            z,
+            ..self.clone()
+        }
+    }
+
+    pub fn translate(&mut self, offset: CanvasVector) {
+        self.points.iter_mut().for_each(|p| *p += offset);
+    }
+
+    /// Removes points where the line turns by less than `max_angle` radians
+    /// as they don't change its shape.
+    pub fn drop_collinear(&mut self, max_angle: f32) {
+        if self.points.len() < 3 {
+            return;
+        }
+
+        let mut kept = vec![self.points[0]];
+        for seg in self.points.windows(2).skip(1) {
+            let prev = *kept.last().unwrap();
+            let turn = (seg[0] - prev).angle_to(seg[1] - seg[0]);
+            if turn.radians.abs() > max_angle {
+                kept.push(seg[0]);
+            }
+        }
+        kept.push(*self.points.last().unwrap());
+
+        self.points = kept;
+    }
+
+    /// Replaces the line with one between its ends if no point is
+    /// further than `max_deviation` from it. Returns whether it did.
+    pub fn straighten(&mut self, max_deviation: f32) -> bool {
+        let (Some(&start), Some(&end)) = (self.points.first(), self.points.last()) else {
+            return false;
+        };
+        let dir = end - start;
+        let len = dir.length();
+        // closed or tiny strokes aren't meant to be lines
+        if self.points.len() < 3 || len < 4.0 * max_deviation {
+            return false;
+        }
+
+        let straight = self
+            .points
+            .iter()
+            .all(|&p| dir.cross(p - start).abs() / len <= max_deviation);
+        if straight {
+            self.points = vec![start, end];
+        }
+        straight
+    }
+
+    /// Whether the line comes within `radius` of the segment from `a` to `b`,
+    /// going straight between its points.
+    pub fn touches(&self, a: CanvasPoint, b: CanvasPoint, radius: f32) -> bool {
+        let reach = radius + self.brush.thickness.0 / 2.0;
+        let swept = CanvasBox::from_points([a, b]).inflate(reach, reach);
+        if !self.bounds().intersects(&swept) {
+            return false;
+        }
+
+        match self.points.as_slice() {
+            [p] => point_segment_distance(*p, a, b) <= reach,
+            points => points
+                .windows(2)
+                .any(|s| segment_distance(s[0], s[1], a, b) <= reach),
+        }
+    }
+
+    /// The pieces of the line left after erasing a circle around `center`,
+    /// `None` if the circle doesn't touch it.
+    pub fn erase(&self, center: CanvasPoint, radius: f32) -> Option<Vec<Vec<CanvasPoint>>> {
+        if !self.bounds().inflate(radius, radius).contains(center) {
+            return None;
+        }
+        let reach = radius + self.brush.thickness.0 / 2.0;
+        let inside = |p: CanvasPoint| (p - center).length() < reach;
+        if let [p] = self.points.as_slice() {
+            return inside(*p).then(Vec::new);
+        }
+
+        let mut pieces = vec![];
+        let mut piece = vec![];
+        if !inside(self.points[0]) {
+            piece.push(self.points[0]);
+        }
+        let mut erased = piece.is_empty();
+        for seg in self.points.windows(2) {
+            let (a, b) = (seg[0], seg[1]);
+            let dir = b - a;
+            // where the segment enters and leaves the circle, from solving
+            // |a + t * dir - center| = reach
+            let to_a = a - center;
+            let (qa, qb) = (dir.square_length(), 2.0 * dir.dot(to_a));
+            let disc = qb * qb - 4.0 * qa * (to_a.square_length() - reach * reach);
+            let (t0, t1) = if qa > 0.0 && disc > 0.0 {
+                let root = disc.sqrt();
+                ((-qb - root) / (2.0 * qa), (-qb + root) / (2.0 * qa))
+            } else {
+                (1.0, 1.0)
+            };
+            if t0 >= 1.0 || t1 <= 0.0 {
+                piece.push(b);
+                continue;
+            }
+
+            erased = true;
+            if t0 > 0.0 {
+                piece.push(a + dir * t0);
+            }
+            if piece.len() > 1 {
+                pieces.push(mem::take(&mut piece));
+            }
+            piece.clear();
+            if t1 < 1.0 {
+                piece.extend([a + dir * t1, b]);
+            }
+        }
+        if piece.len() > 1 {
+            pieces.push(piece);
+        }
+
+        erased.then_some(pieces)
+    }
+
+    fn draw_stroke(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        match self.points.len() {
+            0 => {}
+            1 => self.draw_single(d, camera),
+            _ if QUALITY.get() == Quality::Low => self.draw_fast(d, camera),
+            _ if self.segmented => self.draw_shorter(d, camera),
+            2..4 => self.draw_short_spline(d, camera),
+            4.. => self.draw_longer(d, camera),
+        }
+    }
+
+    fn draw_longer(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        if self.points.len() < 4 {
+            self.draw_short_spline(d, camera);
+            return;
+        }
+
+        let thickness = self.brush.thickness.to_screen(camera).0;
+        let area = view_area(d);
+        // a segment is shaped by its neighbours so check all of them
+        let visible = |ctrl: &[Vector2]| {
+            let min_x = ctrl.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+            let max_x = ctrl.iter().map(|p| p.x).fold(f32::MIN, f32::max);
+            let min_y = ctrl.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+            let max_y = ctrl.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+
+            max_x >= area.x - thickness
+                && min_x <= area.x + area.width + thickness
+                && max_y >= area.y - thickness
+                && min_y <= area.y + area.height + thickness
+        };
+
+        SPLINE_POINTS.with_borrow_mut(|pts| {
+            pts.clear();
+            let first = (self.points[0] * 2.0 - self.points[1]).to_point();
+            pts.extend(
+                std::iter::once(&first)
+                    .chain(self.points.iter())
+                    .map(|p| Vector2::from(p.to_screen(camera).into_vec2())),
+            );
+
+            // the segment between pts[i] and pts[i + 1] needs pts[i - 1]..=pts[i + 2],
+            // draw every run of visible segments on its own
+            let mut run = None;
+            for i in 1..pts.len() - 2 {
+                match (visible(&pts[i - 1..=i + 2]), run) {
+                    (true, None) => run = Some(i),
+                    (false, Some(start)) => {
+                        d.draw_spline_catmull_rom(
+                            &pts[start - 1..=i + 1],
+                            thickness,
+                            self.brush.color,
+                        );
+                        run = None;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(start) = run {
+                d.draw_spline_catmull_rom(&pts[start - 1..], thickness, self.brush.color);
+            }
+        });
+
+        if QUALITY.get() == Quality::High {
+            // round off where the spline starts and ends
+            let r = thickness / 2.0;
+            let first = self.points[0].to_screen(camera);
+            let last = self.points[self.points.len() - 2].to_screen(camera);
+            d.draw_circle_v(first.into_vec2(), r, self.brush.color);
+            d.draw_circle_v(last.into_vec2(), r, self.brush.color);
+        }
+    }
+
+    fn draw_fast(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let thickness = self.brush.thickness.to_screen(camera).0;
+        for seg in self.points.windows(2) {
+            d.draw_line_ex(
+                seg[0].to_screen(camera).into_vec2(),
+                seg[1].to_screen(camera).into_vec2(),
+                thickness,
+                self.brush.color,
+            );
+        }
+    }
+
+    /// Like `draw_longer` but mirrors both ends so that the spline
+    /// reaches every one of the few points.
+    fn draw_short_spline(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let n = self.points.len();
+        let thickness = self.brush.thickness.to_screen(camera).0;
+
+        SPLINE_POINTS.with_borrow_mut(|pts| {
+            pts.clear();
+            let first = (self.points[0] * 2.0 - self.points[1]).to_point();
+            let last = (self.points[n - 1] * 2.0 - self.points[n - 2]).to_point();
+            pts.extend(
+                std::iter::once(&first)
+                    .chain(self.points.iter())
+                    .chain(std::iter::once(&last))
+                    .map(|p| Vector2::from(p.to_screen(camera).into_vec2())),
+            );
+            d.draw_spline_catmull_rom(&pts[..], thickness, self.brush.color);
+        });
+
+        if QUALITY.get() == Quality::High {
+            let r = thickness / 2.0;
+            let first = self.points[0].to_screen(camera);
+            let last = self.points[n - 1].to_screen(camera);
+            d.draw_circle_v(first.into_vec2(), r, self.brush.color);
+            d.draw_circle_v(last.into_vec2(), r, self.brush.color);
+        }
+    }
+
+    fn draw_shorter(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let r = self.brush.thickness.to_screen(camera) / 2.0;
+        // one circle per joint, drawing them twice darkens translucent colors
+        for p in &self.points {
+            d.draw_circle_v(p.to_screen(camera).into_vec2(), r.0, self.brush.color);
+        }
+        for seg in self.points.windows(2) {
+            let p0 = seg[0].to_screen(camera);
+            let p1 = seg[1].to_screen(camera);
+            d.draw_line_ex(
+                p0.into_vec2(),
+                p1.into_vec2(),
+                self.brush.thickness.to_screen(camera).0,
+                self.brush.color,
+            );
+        }
+    }
+
+    fn draw_single(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        assert!(self.points.len() == 1);
+
+        let p = self.points[0].to_screen(camera);
+        let r = self.brush.thickness.to_screen(camera) / 2.0;
+
+        d.draw_circle_v(p.into_vec2(), r.0, self.brush.color);
+    }
+}
+
+impl Bounds for Line {
+    fn bounds(&self) -> CanvasBox {
+        debug_assert!(!self.points.is_empty());
+
+        let r = self.brush.thickness.0;
+
+        let min_x = self
+            .points
+            .iter()
+            .map(|a| a.x)
+            .min_by(f32::total_cmp)
+            .unwrap()
+            - r;
+        let min_y = self
+            .points
+            .iter()
+            .map(|a| a.y)
+            .min_by(f32::total_cmp)
+            .unwrap()
+            - r;
+        let max_x = self
+            .points
+            .iter()
+            .map(|a| a.x)
+            .max_by(f32::total_cmp)
+            .unwrap()
+            + r;
+        let max_y = self
+            .points
+            .iter()
+            .map(|a| a.y)
+            .max_by(f32::total_cmp)
+            .unwrap()
+            + r;
+
+        CanvasBox::new(
+            CanvasPoint::new(min_x, min_y),
+            CanvasPoint::new(max_x, max_y),
+        )
+    }
+}
+
+impl Drawable for Line {
+    fn z(&self) -> usize {
+        self.z
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        match self.brush.mode {
+            BrushMode::Pen => self.draw_stroke(d, camera),
+            BrushMode::Highlighter => {
+                draw_layered(d, self.brush.mode.opacity(), |d| {
+                    self.draw_stroke(d, camera)
+                });
+            }
+        }
+    }
+}
+
+fn point_segment_distance(p: CanvasPoint, a: CanvasPoint, b: CanvasPoint) -> f32 {
+    let ab = b - a;
+    let t = if ab.square_length() > 0.0 {
+        ((p - a).dot(ab) / ab.square_length()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (p - (a + ab * t)).length()
+}
+
+/// Shortest distance between the segments `a`-`b` and `c`-`d`.
+fn segment_distance(a: CanvasPoint, b: CanvasPoint, c: CanvasPoint, d: CanvasPoint) -> f32 {
+    let crosses = (b - a).cross(c - a) * (b - a).cross(d - a) < 0.0
+        && (d - c).cross(a - c) * (d - c).cross(b - c) < 0.0;
+    if crosses {
+        return 0.0;
+    }
+
+    point_segment_distance(a, c, d)
+        .min(point_segment_distance(b, c, d))
+        .min(point_segment_distance(c, a, b))
+        .min(point_segment_distance(d, a, b))
+}
+
+/// Sets the quality lines are drawn with, so changing it in the config
+/// applies to the lines already drawn.
+pub fn set_quality(quality: Quality) {
+    QUALITY.set(quality);
+}
+
+/// Redirects drawing into `target` seen through `view` until `end_target`.
+pub fn begin_target(target: ffi::RenderTexture2D, view: ffi::Camera2D) {
+    TARGET.set(Some((target, view)));
+    unsafe {
+        ffi::BeginTextureMode(target);
+        ffi::BeginMode2D(view);
+    }
+}
+
+pub fn end_target() {
+    TARGET.set(None);
+    unsafe {
+        ffi::EndMode2D();
+        ffi::EndTextureMode();
+    }
+}
+
+/// The part of screen space that ends up drawn, the window or the area
+/// seen by the current target.
+fn view_area(d: &RaylibDrawHandle) -> ffi::Rectangle {
+    match TARGET.get() {
+        Some((t, view)) => ffi::Rectangle {
+            x: view.target.x - view.offset.x / view.zoom,
+            y: view.target.y - view.offset.y / view.zoom,
+            width: t.texture.width as f32 / view.zoom,
+            height: t.texture.height as f32 / view.zoom,
+        },
+        None => ffi::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: d.get_screen_width() as f32,
+            height: d.get_screen_height() as f32,
+        },
+    }
+}
+
+/// Runs `draw` on a separate layer which then gets drawn at `opacity`,
+/// so overlapping parts don't end up more opaque than the rest.
+fn draw_layered(d: &mut RaylibDrawHandle, opacity: f32, draw: impl FnOnce(&mut RaylibDrawHandle)) {
+    let target = TARGET.get();
+    let (width, height) = match target {
+        Some((t, _)) => (t.texture.width, t.texture.height),
+        None => (d.get_screen_width(), d.get_screen_height()),
+    };
+    let layer = match LAYER.get() {
+        Some(l) if l.texture.width == width && l.texture.height == height => l,
+        old => unsafe {
+            if let Some(old) = old {
+                ffi::UnloadRenderTexture(old);
+            }
+            ffi::LoadRenderTexture(width, height)
+        },
+    };
+    LAYER.set(Some(layer));
+
+    unsafe {
+        if target.is_some() {
+            ffi::EndMode2D();
+        }
+        ffi::BeginTextureMode(layer);
+    }
+    d.clear_background(Color::BLANK);
+    if let Some((_, view)) = target {
+        unsafe { ffi::BeginMode2D(view) };
+    }
+    draw(d);
+    unsafe {
+        if target.is_some() {
+            ffi::EndMode2D();
+        }
+        ffi::EndTextureMode();
+        if let Some((t, _)) = target {
+            ffi::BeginTextureMode(t);
+        }
+        // render textures are stored upside down
+        let source = ffi::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: width as f32,
+            height: -height as f32,
+        };
+        ffi::DrawTextureRec(
+            layer.texture,
+            source,
+            ffi::Vector2 { x: 0.0, y: 0.0 },
+            Color::WHITE.alpha(opacity).into(),
+        );
+        if let Some((_, view)) = target {
+            ffi::BeginMode2D(view);
+        }
+    }
+}
+
+/// Applies the configured sampling settings to a freshly loaded image texture.
+pub fn configure_texture(texture: &mut Texture2D, thread: &RaylibThread, config: &Config) {
+    if config.mipmaps {
+        texture.gen_texture_mipmaps();
+    }
+    texture.set_texture_filter(thread, config.texture_filter);
+}
+
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub pos: CanvasPoint,
+    pub texture: Rc<Texture2D>,
+    pub is_selected: bool,
+    pub scale: Length<CanvasSpace>,
+    pub id: ImageId,
+    pub z: usize,
+    pub opacity: f32,
+    border_color: Color,
+}
+
+impl Image {
     pub fn new(
         pos: CanvasPoint,
         texture: Texture2D,
@@ -342,6 +1460,7 @@ impl Image {
             scale,
             id,
             z,
+            opacity: 1.0,
             border_color: config.colors[0],
         }
     }
@@ -357,6 +1476,34 @@ impl Image {
     pub fn in_bounds(&self, point: CanvasPoint) -> bool {
         self.bounds().contains(point)
     }
+
+    /// Draws only the part of the image inside `area`.
+    pub fn draw_within(&self, d: &mut RaylibDrawHandle, camera: &Camera, area: CanvasBox) {
+        let Some(part) = self.bounds().intersection(&area) else {
+            return;
+        };
+        let source = ffi::Rectangle {
+            x: (part.min.x - self.pos.x) / self.scale.0,
+            y: (part.min.y - self.pos.y) / self.scale.0,
+            width: part.width() / self.scale.0,
+            height: part.height() / self.scale.0,
+        };
+        let dest = part.to_screen(camera).to_rect();
+
+        d.draw_texture_pro(
+            &*self.texture,
+            source,
+            ffi::Rectangle {
+                x: dest.origin.x,
+                y: dest.origin.y,
+                width: dest.size.width,
+                height: dest.size.height,
+            },
+            Vector2::zero(),
+            0.0,
+            Color::WHITE.alpha(self.opacity),
+        );
+    }
 }
 
 impl Bounds for Image {
@@ -381,7 +1528,7 @@ impl Drawable for Image {
             rect.min().into_vec2(),
             0.0,
             self.scale.to_screen(camera).0,
-            Color::WHITE,
+            Color::WHITE.alpha(self.opacity),
         );
 
         if self.is_selected {
@@ -399,16 +1546,30 @@ impl Drawable for Image {
 #[derive(Debug, Clone, Copy)]
 pub struct Eraser {
     rect: FilledRect,
+    pub id: EraserId,
     z: usize,
 }
 
 impl Eraser {
-    pub fn new(rect: Rect<CanvasSpace>, color: Color, z: usize) -> Self {
+    pub fn new(rect: Rect<CanvasSpace>, color: Color, id: EraserId, z: usize) -> Self {
         Self {
             rect: FilledRect::new(rect, color),
+            id,
             z,
         }
     }
+
+    pub fn copy_as(&self, id: EraserId, z: usize) -> Self {
+        Self { id, z, ..*self }
+    }
+
+    pub fn color(&self) -> Color {
+        self.rect.color
+    }
+
+    pub fn translate(&mut self, offset: CanvasVector) {
+        self.rect.rect.origin += offset;
+    }
 }
 
 impl Bounds for Eraser {
@@ -425,4 +1586,602 @@ impl Drawable for Eraser {
     fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
         self.rect.draw(d, camera);
     }
+
+    fn erased(&self) -> Option<CanvasBox> {
+        Some(self.bounds())
+    }
+}
+
+/// Draws `drawable`, keeping `tracing` visible through erasers.
+pub fn draw_over_tracing(
+    drawable: &dyn Drawable,
+    tracing: Option<&Image>,
+    d: &mut RaylibDrawHandle,
+    camera: &Camera,
+) {
+    drawable.draw(d, camera);
+    if let Some(tracing) = tracing
+        && let Some(area) = drawable.erased()
+    {
+        tracing.draw_within(d, camera, area);
+    }
+}
+
+/// Makes `area` fully transparent, drawing with a transparent color
+/// would leave what's underneath.
+pub fn clear_box(d: &mut RaylibDrawHandle, camera: &Camera, area: CanvasBox) {
+    let rect = area.to_screen(camera);
+    unsafe {
+        // GL_ONE, GL_ZERO, GL_FUNC_ADD: the color replaces what's there
+        ffi::rlSetBlendFactors(1, 0, 0x8006);
+        ffi::BeginBlendMode(ffi::BlendMode::BLEND_CUSTOM as i32);
+    }
+    d.draw_rectangle_v(rect.min.into_vec2(), rect.size().into_vec2(), Color::BLANK);
+    unsafe { ffi::EndBlendMode() };
+}
+
+/// Rounds `min` up to the nearest 1, 2 or 5 times a power of ten.
+pub fn nice_step(min: f32) -> f32 {
+    let magnitude = 10f32.powf(min.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|&step| step >= min)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Scales a size in pixels by the UI scale.
+pub fn ui_px(px: i32, ui_scale: f32) -> i32 {
+    (px as f32 * ui_scale).round() as i32
+}
+
+fn format_coord(value: f32, step: f32) -> String {
+    if step >= 1.0 {
+        format!("{}", value.round() as i64)
+    } else {
+        let precision = (-step.log10()).ceil() as usize;
+        format!("{value:.precision$}")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rulers {
+    pub size: ScreenSize,
+    pub background: Color,
+    pub color: Color,
+    pub ui_scale: f32,
+}
+
+impl Rulers {
+    const WIDTH: f32 = 20.0;
+    const FONT_SIZE: i32 = 10;
+    // minimal screen distance between two labelled ticks
+    const MIN_SPACING: f32 = 80.0;
+    const SUBDIVISIONS: i64 = 5;
+
+    pub fn new(size: ScreenSize, background: Color, color: Color, ui_scale: f32) -> Self {
+        Self {
+            size,
+            background,
+            color,
+            ui_scale,
+        }
+    }
+
+    /// Calls `f` with the canvas value and whether it's a major tick for
+    /// every tick between `min` and `max`.
+    fn ticks(min: f32, max: f32, step: f32, mut f: impl FnMut(f32, bool)) {
+        let minor = step / Self::SUBDIVISIONS as f32;
+        let first = (min / minor).floor() as i64;
+        let last = (max / minor).ceil() as i64;
+        for i in first..=last {
+            f(i as f32 * minor, i % Self::SUBDIVISIONS == 0);
+        }
+    }
+}
+
+impl InView for Rulers {
+    fn is_in_view(&self, _camera: &Camera) -> bool {
+        // always visible since it's in ScreenSpace
+        true
+    }
+}
+
+impl Drawable for Rulers {
+    fn z(&self) -> usize {
+        0
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let w = Self::WIDTH * self.ui_scale;
+        let font = ui_px(Self::FONT_SIZE, self.ui_scale);
+        let scale = CanvasLength::new(1.0).to_screen(camera).0;
+        let step = nice_step(Self::MIN_SPACING * self.ui_scale / scale);
+        let min = ScreenPoint::origin().to_canvas(camera);
+        let max = ScreenPoint::new(self.size.width, self.size.height).to_canvas(camera);
+        let background = self.background.brightness(0.1);
+
+        d.draw_rectangle_v(
+            Vector2::new(0.0, 0.0),
+            Vector2::new(self.size.width, w),
+            background,
+        );
+        d.draw_rectangle_v(
+            Vector2::new(0.0, 0.0),
+            Vector2::new(w, self.size.height),
+            background,
+        );
+
+        Self::ticks(min.x, max.x, step, |x, major| {
+            let sx = CanvasPoint::new(x, 0.0).to_screen(camera).x;
+            if sx < w {
+                return;
+            }
+            let len = if major { w } else { w / 3.0 };
+            d.draw_line_v(Vector2::new(sx, w - len), Vector2::new(sx, w), self.color);
+            if major {
+                d.draw_text(&format_coord(x, step), sx as i32 + 2, 2, font, self.color);
+            }
+        });
+
+        Self::ticks(min.y, max.y, step, |y, major| {
+            let sy = CanvasPoint::new(0.0, y).to_screen(camera).y;
+            if sy < w {
+                return;
+            }
+            let len = if major { w } else { w / 3.0 };
+            d.draw_line_v(Vector2::new(w - len, sy), Vector2::new(w, sy), self.color);
+            if major {
+                d.draw_text(&format_coord(y, step), 2, sy as i32 + 2, font, self.color);
+            }
+        });
+    }
+}
+
+/// A bar in the bottom left corner showing a round canvas length.
+#[derive(Debug, Clone, Copy)]
+pub struct ScaleBar {
+    pub size: ScreenSize,
+    pub color: Color,
+    pub ui_scale: f32,
+}
+
+impl ScaleBar {
+    const MARGIN: f32 = 20.0;
+    const FONT_SIZE: i32 = 10;
+    const MIN_LENGTH: f32 = 100.0;
+}
+
+impl InView for ScaleBar {
+    fn is_in_view(&self, _camera: &Camera) -> bool {
+        // always visible since it's in ScreenSpace
+        true
+    }
+}
+
+impl Drawable for ScaleBar {
+    fn z(&self) -> usize {
+        0
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let scale = CanvasLength::new(1.0).to_screen(camera).0;
+        let step = nice_step(Self::MIN_LENGTH * self.ui_scale / scale);
+        let len = step * scale;
+
+        let ui = self.ui_scale;
+        let font = ui_px(Self::FONT_SIZE, ui);
+        let x = Self::MARGIN * ui;
+        let y = self.size.height - Self::MARGIN * ui;
+        d.draw_line_ex(
+            Vector2::new(x, y),
+            Vector2::new(x + len, y),
+            2.0 * ui,
+            self.color,
+        );
+        for end in [x, x + len] {
+            d.draw_line_v(
+                Vector2::new(end, y - 5.0 * ui),
+                Vector2::new(end, y),
+                self.color,
+            );
+        }
+        d.draw_text(
+            &format!("{} units", format_coord(step, step)),
+            x as i32,
+            (y - 8.0 * ui) as i32 - font,
+            font,
+            self.color,
+        );
+    }
+}
+
+/// The zoom and the brush in the bottom right corner.
+pub struct StatusBar {
+    pub size: ScreenSize,
+    pub brush: Brush<CanvasSpace>,
+    pub color: Color,
+    pub ui_scale: f32,
+}
+
+impl StatusBar {
+    const MARGIN: f32 = 20.0;
+    const FONT_SIZE: i32 = 10;
+    const SWATCH: f32 = 6.0;
+}
+
+impl InView for StatusBar {
+    fn is_in_view(&self, _camera: &Camera) -> bool {
+        // always visible since it's in ScreenSpace
+        true
+    }
+}
+
+impl Drawable for StatusBar {
+    fn z(&self) -> usize {
+        0
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let ui = self.ui_scale;
+        let font = ui_px(Self::FONT_SIZE, ui);
+        let text = format!(
+            "{:.0}%  {:.1} px",
+            camera.zoom().0 * 100.0,
+            self.brush.thickness.0
+        );
+        let width = d.measure_text(&text, font) as f32;
+        let x = self.size.width - Self::MARGIN * ui - width;
+        let y = self.size.height - Self::MARGIN * ui - font as f32;
+        d.draw_text(&text, x as i32, y as i32, font, self.color);
+
+        let swatch = Self::SWATCH * ui;
+        let center = Vector2::new(x - 2.0 * swatch, y + font as f32 / 2.0);
+        d.draw_circle_v(
+            center,
+            swatch,
+            self.brush.color.alpha(self.brush.mode.opacity()),
+        );
+        d.draw_circle_lines(center.x as i32, center.y as i32, swatch, self.color);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScreenNote {
+    pub text: String,
+    pub editing: bool,
+}
+
+impl ScreenNote {
+    const FONT_SIZE: i32 = 20;
+    const MARGIN: i32 = 10;
+
+    /// Draws the notes stacked in the top right corner of the window.
+    pub fn draw_all(
+        notes: &[ScreenNote],
+        d: &mut RaylibDrawHandle,
+        size: ScreenSize,
+        background: Color,
+        color: Color,
+        ui_scale: f32,
+    ) {
+        let font = ui_px(Self::FONT_SIZE, ui_scale);
+        let margin = ui_px(Self::MARGIN, ui_scale);
+        let mut y = margin;
+        for note in notes {
+            let text = if note.editing {
+                format!("{}_", note.text)
+            } else {
+                note.text.clone()
+            };
+            let width = d.measure_text(&text, font);
+            let x = size.width as i32 - width - 2 * margin;
+
+            d.draw_rectangle(
+                x - margin / 2,
+                y - margin / 2,
+                width + margin,
+                font + margin,
+                background.brightness(0.1),
+            );
+            d.draw_text(&text, x, y, font, color);
+
+            y += font + 2 * margin;
+        }
+    }
+}
+
+/// A short message shown at the bottom of the window for a few seconds.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub text: String,
+    shown: Instant,
+}
+
+impl Notification {
+    const DURATION: Duration = Duration::from_secs(3);
+    const FONT_SIZE: i32 = 20;
+    const MARGIN: i32 = 10;
+
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            shown: Instant::now(),
+        }
+    }
+
+    pub fn expired(&self) -> bool {
+        self.shown.elapsed() > Self::DURATION
+    }
+
+    pub fn draw(
+        &self,
+        d: &mut RaylibDrawHandle,
+        size: ScreenSize,
+        background: Color,
+        color: Color,
+        ui_scale: f32,
+    ) {
+        let font = ui_px(Self::FONT_SIZE, ui_scale);
+        let margin = ui_px(Self::MARGIN, ui_scale);
+        let width = d.measure_text(&self.text, font);
+        let x = (size.width as i32 - width) / 2;
+        let y = size.height as i32 - font - 2 * margin;
+
+        d.draw_rectangle(
+            x - margin / 2,
+            y - margin / 2,
+            width + margin,
+            font + margin,
+            background.brightness(0.1),
+        );
+        d.draw_text(&self.text, x, y, font, color);
+    }
+}
+
+/// The colors to cycle through with the current one raised.
+#[derive(Debug, Clone, Copy)]
+pub struct PaletteStrip<'a> {
+    pub colors: &'a [Color],
+    pub current: usize,
+    // from 0 to 1 over the time it's shown
+    pub progress: f32,
+    pub ui_scale: f32,
+}
+
+impl PaletteStrip<'_> {
+    pub const DURATION: Duration = Duration::from_millis(1500);
+    const SWATCH: i32 = 24;
+    const GAP: i32 = 6;
+    const RAISE: i32 = 8;
+    // fraction of the duration spent fading out
+    const FADE: f32 = 0.3;
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, size: ScreenSize, background: Color) {
+        let swatch = ui_px(Self::SWATCH, self.ui_scale);
+        let gap = ui_px(Self::GAP, self.ui_scale);
+        let raise = ui_px(Self::RAISE, self.ui_scale);
+        let alpha = ((1.0 - self.progress) / Self::FADE).min(1.0);
+        let n = self.colors.len() as i32;
+        let width = n * swatch + (n - 1) * gap;
+        let x = (size.width as i32 - width) / 2;
+        let y = swatch + raise;
+
+        d.draw_rectangle(
+            x - gap,
+            y - raise - gap,
+            width + 2 * gap,
+            swatch + raise + 2 * gap,
+            background.brightness(0.1).alpha(alpha),
+        );
+        for (i, &color) in self.colors.iter().enumerate() {
+            let sx = x + i as i32 * (swatch + gap);
+            let sy = if i == self.current { y - raise } else { y };
+            d.draw_rectangle(sx, sy, swatch, swatch, color.alpha(alpha));
+        }
+    }
+}
+
+/// Fades the window edges into `color`.
+#[derive(Debug, Clone, Copy)]
+pub struct Vignette {
+    pub size: ScreenSize,
+    pub color: Color,
+}
+
+impl Vignette {
+    // fraction of the smaller window dimension covered by the fade
+    const EXTENT: f32 = 0.15;
+    const ALPHA: f32 = 0.8;
+}
+
+impl InView for Vignette {
+    fn is_in_view(&self, _camera: &Camera) -> bool {
+        // always visible since it's in ScreenSpace
+        true
+    }
+}
+
+impl Drawable for Vignette {
+    fn z(&self) -> usize {
+        0
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, _camera: &Camera) {
+        let (w, h) = (self.size.width as i32, self.size.height as i32);
+        let e = (self.size.width.min(self.size.height) * Self::EXTENT) as i32;
+        let edge = self.color.alpha(Self::ALPHA);
+        let clear = self.color.alpha(0.0);
+
+        d.draw_rectangle_gradient_v(0, 0, w, e, edge, clear);
+        d.draw_rectangle_gradient_v(0, h - e, w, e, clear, edge);
+        d.draw_rectangle_gradient_h(0, 0, e, h, edge, clear);
+        d.draw_rectangle_gradient_h(w - e, 0, e, h, clear, edge);
+    }
+}
+
+/// A list of clickable labels in screen space.
+#[derive(Debug, Clone, Copy)]
+pub struct Menu {
+    pub pos: ScreenPoint,
+    pub items: &'static [&'static str],
+    pub width: f32,
+    pub hovered: Option<usize>,
+    pub background: Color,
+    pub color: Color,
+    ui_scale: f32,
+}
+
+impl Menu {
+    const FONT_SIZE: i32 = 20;
+    const PADDING: f32 = 6.0;
+
+    pub fn new(
+        rl: &RaylibHandle,
+        pos: ScreenPoint,
+        items: &'static [&'static str],
+        background: Color,
+        color: Color,
+        ui_scale: f32,
+    ) -> Self {
+        let width = items
+            .iter()
+            .map(|i| rl.measure_text(i, ui_px(Self::FONT_SIZE, ui_scale)))
+            .max()
+            .unwrap_or(0) as f32
+            + 2.0 * Self::PADDING * ui_scale;
+
+        // keep the whole menu inside the window
+        let size = ScreenSize::new(rl.get_screen_width() as f32, rl.get_screen_height() as f32);
+        let height = items.len() as f32 * Self::item_height(ui_scale);
+        let pos = ScreenPoint::new(
+            pos.x.min(size.width - width).max(0.0),
+            pos.y.min(size.height - height).max(0.0),
+        );
+
+        Self {
+            pos,
+            items,
+            width,
+            hovered: None,
+            background,
+            color,
+            ui_scale,
+        }
+    }
+
+    fn item_height(ui_scale: f32) -> f32 {
+        (Self::FONT_SIZE as f32 + 2.0 * Self::PADDING) * ui_scale
+    }
+
+    pub fn item_at(&self, p: ScreenPoint) -> Option<usize> {
+        let (dx, dy) = (p.x - self.pos.x, p.y - self.pos.y);
+        if dx < 0.0 || dx > self.width || dy < 0.0 {
+            return None;
+        }
+
+        let idx = (dy / Self::item_height(self.ui_scale)) as usize;
+        (idx < self.items.len()).then_some(idx)
+    }
+}
+
+impl InView for Menu {
+    fn is_in_view(&self, _camera: &Camera) -> bool {
+        // always visible since it's in ScreenSpace
+        true
+    }
+}
+
+impl Drawable for Menu {
+    fn z(&self) -> usize {
+        0
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, _camera: &Camera) {
+        let h = Self::item_height(self.ui_scale);
+        let padding = Self::PADDING * self.ui_scale;
+        for (i, item) in self.items.iter().enumerate() {
+            let y = self.pos.y + i as f32 * h;
+            let background = if self.hovered == Some(i) {
+                self.background.brightness(0.2)
+            } else {
+                self.background.brightness(0.1)
+            };
+
+            d.draw_rectangle_v(
+                Vector2::new(self.pos.x, y),
+                Vector2::new(self.width, h),
+                background,
+            );
+            d.draw_text(
+                item,
+                (self.pos.x + padding) as i32,
+                (y + padding) as i32,
+                ui_px(Self::FONT_SIZE, self.ui_scale),
+                self.color,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(points: &[(f32, f32)]) -> Line {
+        let brush = Brush {
+            color: Color::WHITE,
+            thickness: CanvasLength::new(1.0),
+            mode: BrushMode::Pen,
+        };
+        let id = Contents::new().next_line_id();
+        let mut line = Line::new(CanvasPoint::new(points[0].0, points[0].1), brush, id, 0);
+        line.points = points
+            .iter()
+            .map(|&(x, y)| CanvasPoint::new(x, y))
+            .collect();
+        line
+    }
+
+    #[test]
+    fn ellipse_outline_touches_its_box() {
+        let brush = stroke(&[(0.0, 0.0)]).brush;
+        let rect = CanvasBox::new(CanvasPoint::new(0.0, 0.0), CanvasPoint::new(4.0, 2.0));
+        let ellipse = Ellipse::new(rect, brush, Contents::new().next_shape_id(), 0);
+        let points = ellipse.outline().collect::<Vec<_>>();
+        assert_eq!(points.first(), points.last());
+        let outline = CanvasBox::from_points(&points);
+        assert!((outline.min - rect.min).length() < 1e-4);
+        assert!((outline.max - rect.max).length() < 1e-4);
+    }
+
+    #[test]
+    fn drop_collinear_keeps_ends_of_a_straight_stroke() {
+        let mut line = stroke(&[
+            (0.0, 0.0),
+            (1.0, 0.01),
+            (2.0, 0.0),
+            (3.0, -0.01),
+            (4.0, 0.0),
+        ]);
+        line.drop_collinear(0.05);
+        assert_eq!(
+            line.points,
+            [CanvasPoint::new(0.0, 0.0), CanvasPoint::new(4.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn drop_collinear_keeps_corners() {
+        let mut line = stroke(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (2.0, 2.0)]);
+        line.drop_collinear(0.05);
+        assert_eq!(
+            line.points,
+            [
+                CanvasPoint::new(0.0, 0.0),
+                CanvasPoint::new(2.0, 0.0),
+                CanvasPoint::new(2.0, 2.0)
+            ]
+        );
+    }
 }
@@ -1,8 +1,11 @@
 use std::rc::Rc;
 
-use raylib::{RaylibHandle, ffi::KeyboardKey};
+use raylib::{
+    RaylibHandle,
+    ffi::{KeyboardKey, MouseButton},
+};
 
-use crate::config::Config;
+use crate::{config::Config, export::ExportArea};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Action {
@@ -10,30 +13,111 @@ pub enum Action {
     Redo,
     NextColor,
     PrevColor,
+    SelectColor(u8),
     Paste,
     Remove,
     UpLayer,
     DownLayer,
+    AddNote,
+    RemoveNote,
+    Lasso,
+    Copy,
+    Cut,
+    Stats,
+    History,
+    Playback,
+    ExtractPalette,
+    Trace,
+    ToggleTracing,
+    ToggleHighlighter,
+    // moves the selected image by this many steps along x and y
+    Nudge(i8, i8),
+    Save,
+    OpenImage,
+    Duplicate,
+    CopyImage,
+    AddText,
+    Export(ExportArea),
+    ExportSvg,
+    SaveText,
+    ToggleTopmost,
+    Checkpoint,
+    RestoreCheckpoint,
+    Clear,
+    ReloadConfig,
+    ResetView,
+    LoadText,
     None,
 }
 
+pub enum TextInput {
+    Typing,
+    Done,
+    Cancel,
+}
+
+/// Applies the characters typed this frame to `text`.
+pub fn edit_text(rl: &mut RaylibHandle, text: &mut String) -> TextInput {
+    while let Some(c) = rl.get_char_pressed() {
+        text.push(c);
+    }
+
+    if rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE)
+        || rl.is_key_pressed_repeat(KeyboardKey::KEY_BACKSPACE)
+    {
+        text.pop();
+    }
+
+    if rl.is_key_pressed(KeyboardKey::KEY_ENTER) || rl.is_key_pressed(KeyboardKey::KEY_KP_ENTER) {
+        TextInput::Done
+    } else if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+        TextInput::Cancel
+    } else {
+        TextInput::Typing
+    }
+}
+
+/// A key or a mouse button that can be part of a combo.
+#[derive(Debug, Clone, Copy)]
+pub enum Key {
+    Keyboard(KeyboardKey),
+    Mouse(MouseButton),
+}
+
+impl Key {
+    fn is_pressed(self, rl: &RaylibHandle) -> bool {
+        match self {
+            Key::Keyboard(k) => rl.is_key_pressed(k),
+            Key::Mouse(b) => rl.is_mouse_button_pressed(b),
+        }
+    }
+
+    fn is_down(self, rl: &RaylibHandle) -> bool {
+        match self {
+            Key::Keyboard(k) => rl.is_key_down(k),
+            Key::Mouse(b) => rl.is_mouse_button_down(b),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Keybind {
-    keys: Box<[Box<[KeyboardKey]>]>,
+    keys: Box<[Box<[Key]>]>,
     action: Action,
 }
 
 impl Keybind {
-    pub fn new(keys: Box<[Box<[KeyboardKey]>]>, action: Action) -> Self {
+    pub fn new(keys: Box<[Box<[Key]>]>, action: Action) -> Self {
         Self { keys, action }
     }
-    pub fn action(&self, rl: &RaylibHandle) -> Option<Action> {
+    /// Returns the action along with the length of the matched combo.
+    pub fn action(&self, rl: &RaylibHandle) -> Option<(Action, usize)> {
         for combo in &self.keys {
             if let Some((&last, rest)) = combo.split_last()
-                && rl.is_key_pressed(last)
-                && rest.iter().all(|&k| rl.is_key_down(k))
+                && last.is_pressed(rl)
+                && rest.iter().all(|k| k.is_down(rl))
             {
-                return Some(self.action);
+                return Some((self.action, combo.len()));
             }
         }
 
@@ -41,6 +125,24 @@ impl Keybind {
     }
 }
 
+/// Keys that have to be held down (as opposed to pressed) for an effect.
+#[derive(Debug)]
+pub struct Modifier {
+    keys: Box<[Box<[Key]>]>,
+}
+
+impl Modifier {
+    pub fn new(keys: Box<[Box<[Key]>]>) -> Self {
+        Self { keys }
+    }
+
+    pub fn is_down(&self, rl: &RaylibHandle) -> bool {
+        self.keys
+            .iter()
+            .any(|combo| combo.iter().all(|k| k.is_down(rl)))
+    }
+}
+
 pub struct InputHandler {
     config: Rc<Config>,
 }
@@ -51,11 +153,12 @@ impl InputHandler {
     }
 
     pub fn interpret(&self, rl: &RaylibHandle) -> Action {
-        for keybind in &self.config.keybinds {
-            if let Some(action) = keybind.action(rl) {
-                return action;
-            }
-        }
-        Action::None
+        // prefer the longest combo so that e.g. shift+n wins over n
+        self.config
+            .keybinds
+            .iter()
+            .filter_map(|k| k.action(rl))
+            .max_by_key(|&(_, len)| len)
+            .map_or(Action::None, |(action, _)| action)
     }
 }
@@ -1,39 +1,174 @@
 #![windows_subsystem = "windows"]
 
 use crate::{clipboard::Clipboard, config::Config, scene::Scene};
-use std::{env, process};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    config::Quality,
+    graphics::{Brush, BrushMode, Contents, Line},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use raylib::{RaylibHandle, RaylibThread, color::Color, core::window, prelude::RaylibDraw};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+use std::{env, path::PathBuf, process};
+#[cfg(not(target_arch = "wasm32"))]
+use widok::{Camera, CanvasLength, CanvasSize, ScreenPoint, ScreenSize, ToCanvas};
 
 mod clipboard;
 mod command;
 mod config;
+mod export;
 mod graphics;
 mod input;
+mod palette;
+mod save;
 mod scene;
+mod selection;
+mod shape;
 mod state;
 
 const WIDTH: u32 = 1280;
 const HEIGHT: u32 = 720;
 
-fn usage(prog_name: &str) {
+fn usage(prog_name: &str) -> ! {
     eprintln!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
     eprintln!("{}", env!("CARGO_PKG_DESCRIPTION"));
     eprintln!();
-    eprintln!("Usage: {prog_name} [config path]");
+    eprintln!("Usage: {prog_name} [config path] [canvas.kajet] [images...]");
+    eprintln!("       {prog_name} --config-inline <config contents> [canvas.kajet] [images...]");
+    eprintln!("       {prog_name} --bench-quality");
+    eprintln!();
+    eprintln!("Use - as the config path to read it from stdin.");
+    eprintln!("The canvas is loaded from the .kajet file and saved back to it.");
+    eprintln!("Image files given after the config get opened on the canvas.");
     process::exit(1);
 }
 
+/// Centers the window on the configured monitor, sized relative to its resolution.
+#[cfg(not(target_arch = "wasm32"))]
+fn place_window(rl: &mut RaylibHandle, config: &Config) {
+    let count = window::get_monitor_count();
+    if count < 1 {
+        return;
+    }
+
+    let monitor = match i32::try_from(config.monitor) {
+        Ok(m) if m < count => m,
+        _ => {
+            eprintln!(
+                "[ERROR] Monitor {} not found, {count} available (using the primary one)",
+                config.monitor
+            );
+            0
+        }
+    };
+
+    let (mw, mh) = (
+        window::get_monitor_width(monitor),
+        window::get_monitor_height(monitor),
+    );
+    // keep the default size if the platform doesn't report the resolution
+    if mw <= 0 || mh <= 0 {
+        return;
+    }
+
+    let width = (mw as f32 * config.window_size) as i32;
+    let height = (mh as f32 * config.window_size) as i32;
+    let pos = window::get_monitor_position(monitor);
+    rl.set_window_size(width, height);
+    rl.set_window_position(
+        pos.x as i32 + (mw - width) / 2,
+        pos.y as i32 + (mh - height) / 2,
+    );
+}
+
+/// Draws the same wavy strokes in every quality mode and prints how long a frame took.
+#[cfg(not(target_arch = "wasm32"))]
+fn bench_quality(rl: &mut RaylibHandle, thread: &RaylibThread) {
+    const LINES: usize = 300;
+    const POINTS: usize = 200;
+    const FRAMES: u32 = 200;
+
+    let size = ScreenSize::new(rl.get_screen_width() as f32, rl.get_screen_height() as f32);
+    let mut camera = Camera::new(CanvasSize::new(size.width, size.height));
+    camera.update(1.0, ScreenPoint::origin(), size);
+
+    let brush = Brush {
+        color: Color::WHITE,
+        thickness: CanvasLength::new(4.0),
+        mode: BrushMode::Pen,
+    };
+    let mut contents = Contents::new();
+    for i in 0..LINES {
+        let y = size.height * i as f32 / LINES as f32;
+        let points = (0..POINTS)
+            .map(|j| {
+                let x = size.width * j as f32 / (POINTS - 1) as f32;
+                ScreenPoint::new(x, y + 20.0 * (x / 30.0).sin()).to_canvas(&camera)
+            })
+            .collect::<Vec<_>>();
+        let id = contents.next_line_id();
+        let mut line = Line::new(points[0], brush, id, i);
+        line.points = points;
+        line.finished = true;
+        contents.lines.push(line);
+    }
+
+    // as fast as possible
+    rl.set_target_fps(0);
+    for quality in [Quality::Low, Quality::Medium, Quality::High] {
+        graphics::set_quality(quality);
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            let mut d = rl.begin_drawing(thread);
+            d.clear_background(Color::BLACK);
+            contents
+                .drawables(false)
+                .for_each(|i| i.draw(&mut d, &camera));
+        }
+        let frame = start.elapsed() / FRAMES;
+        eprintln!(
+            "[INFO] {quality:?}: {:.2} ms per frame",
+            frame.as_secs_f64() * 1000.0
+        );
+    }
+}
+
 fn main() {
     let mut args = env::args();
     let prog_name = args.next().unwrap();
 
-    let config_path = args.next().inspect(|p| {
-        // if someone tries --help or -h
-        if p.starts_with("-") {
-            usage(&prog_name);
+    let mut config = None;
+    // where to reload the config from, None if it can't be read again
+    let mut config_path = Some(None);
+    let mut canvas = None;
+    let mut images = vec![];
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut bench = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            #[cfg(not(target_arch = "wasm32"))]
+            "--bench-quality" => bench = true,
+            "--config-inline" => match args.next() {
+                Some(text) => {
+                    config = Some(Config::from_text(text));
+                    config_path = None;
+                }
+                None => usage(&prog_name),
+            },
+            a if a.ends_with(".kajet") => canvas = Some(PathBuf::from(a)),
+            // if someone tries --help or -h
+            a if a.starts_with("-") && a != "-" => usage(&prog_name),
+            _ if config.is_some() => images.push(arg),
+            _ => {
+                config_path = (arg != "-").then(|| Some(arg.clone()));
+                config = Some(Config::from_file(Some(arg)));
+            }
         }
-    });
+    }
+    let config = config.unwrap_or_else(|| Config::from_file(None));
 
-    let config = match Config::from_file(config_path) {
+    let config = match config {
         Ok(c) => c,
         Err(e) => {
             eprintln!("[ERROR] Couldn't parse config: {e}");
@@ -43,21 +178,43 @@ fn main() {
 
     let clipboard = Clipboard::new();
 
-    let (mut rl, thread) = raylib::init()
+    let mut builder = raylib::init();
+    builder
         .size(WIDTH as i32, HEIGHT as i32)
         .title("Kajet")
         .resizable()
         .msaa_4x()
-        .log_level(raylib::ffi::TraceLogLevel::LOG_WARNING)
-        .build();
+        .log_level(raylib::ffi::TraceLogLevel::LOG_WARNING);
+    #[cfg(not(target_arch = "wasm32"))]
+    if config.transparent {
+        builder.transparent();
+    }
+    let (mut rl, thread) = builder.build();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if bench {
+        bench_quality(&mut rl, &thread);
+        return;
+    }
 
     rl.set_target_fps(config.fps);
+    rl.set_exit_key(config.exit_key);
     #[cfg(not(target_arch = "wasm32"))]
-    rl.hide_cursor();
+    {
+        rl.hide_cursor();
+        place_window(&mut rl, &config);
+    }
 
-    let mut scene = Scene::new(config, clipboard, &mut rl);
+    let mut scene = Scene::new(config, clipboard, &mut rl, &thread);
+    scene.set_config_path(config_path);
+    if let Some(path) = canvas {
+        scene.open(path, &mut rl, &thread);
+    }
+    scene.recover(&mut rl, &thread);
+    scene.open_images(images);
 
-    while !rl.window_should_close() {
+    while !scene.should_quit() {
         scene.process_frame(&thread, &mut rl);
     }
+    scene.close();
 }
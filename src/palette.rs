@@ -0,0 +1,63 @@
+use raylib::color::Color;
+
+fn channel(c: &Color, i: usize) -> u8 {
+    match i {
+        0 => c.r,
+        1 => c.g,
+        _ => c.b,
+    }
+}
+
+/// The channel the colors differ the most in along with that difference.
+fn widest_channel(colors: &[Color]) -> (usize, u8) {
+    (0..3)
+        .map(|i| {
+            let min = colors.iter().map(|c| channel(c, i)).min().unwrap_or(0);
+            let max = colors.iter().map(|c| channel(c, i)).max().unwrap_or(0);
+            (i, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap()
+}
+
+fn average(colors: &[Color]) -> Color {
+    let n = colors.len() as u32;
+    let sum = |i| colors.iter().map(|c| channel(c, i) as u32).sum::<u32>();
+    Color::new(
+        (sum(0) / n) as u8,
+        (sum(1) / n) as u8,
+        (sum(2) / n) as u8,
+        255,
+    )
+}
+
+/// Reduces `pixels` to at most `n` colors using median cut,
+/// the most common ones first.
+pub fn dominant_colors(pixels: &[Color], n: usize) -> Vec<Color> {
+    if pixels.is_empty() || n == 0 {
+        return vec![];
+    }
+
+    let mut boxes = vec![pixels.to_vec()];
+    while boxes.len() < n {
+        let (i, (channel_idx, range)) = boxes
+            .iter()
+            .map(|b| widest_channel(b))
+            .enumerate()
+            .max_by_key(|&(_, (_, range))| range)
+            .unwrap();
+        // what's left are boxes of a single color each
+        if range == 0 {
+            break;
+        }
+
+        let mut b = boxes.swap_remove(i);
+        b.sort_unstable_by_key(|c| channel(c, channel_idx));
+        let upper = b.split_off(b.len() / 2);
+        boxes.push(b);
+        boxes.push(upper);
+    }
+
+    boxes.sort_by_key(|b| std::cmp::Reverse(b.len()));
+    boxes.iter().map(|b| average(b)).collect()
+}
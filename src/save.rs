@@ -0,0 +1,552 @@
+//! A plain text format for the canvas meant to diff well under version control.
+//!
+//! Every primitive is one line of space separated fields, coordinates
+//! are in canvas units and colors are `#rrggbbaa`:
+//!
+//! ```text
+//! line <z> <color> <thickness> <smooth|segmented> <x>,<y> <x>,<y> ...
+//! highlight <z> <color> <thickness> <smooth|segmented> <x>,<y> <x>,<y> ...
+//! eraser <z> <color> <x> <y> <width> <height>
+//! rect <z> <color> <thickness> <x> <y> <width> <height>
+//! ellipse <z> <color> <thickness> <x> <y> <width> <height>
+//! arrow <z> <color> <thickness> <x>,<y> <x>,<y>
+//! text <z> <color> <size> <x>,<y> <text until the end of the line>
+//! image <z> <file or data> <x> <y> <scale>
+//! ```
+//!
+//! Images are either written to separate files next to the text file and
+//! referenced by name, or embedded as `data:image/png;base64,...` to keep
+//! everything in a single `.kajet` file. Empty lines and lines starting
+//! with `#` are ignored.
+
+use std::{
+    ffi::c_void,
+    fmt::Write,
+    path::{self, Path, PathBuf},
+    slice,
+    str::SplitWhitespace,
+};
+
+use raylib::{
+    RaylibHandle, RaylibThread,
+    color::Color,
+    ffi,
+    texture::{self, RaylibTexture2D, Texture2D},
+};
+use widok::{Bounds, CanvasBox, CanvasLength, CanvasPoint, CanvasRect, CanvasSize};
+
+use crate::{
+    config::Config,
+    graphics::{
+        Arrow, Brush, BrushMode, Contents, Drawable, Ellipse, Eraser, Image, Line, Rectangle, Text,
+        configure_texture,
+    },
+};
+
+const PNG_DATA: &str = "data:image/png;base64,";
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Where `to_text` puts the images.
+pub enum ImageOutput<'a> {
+    // files named `<prefix>-<n>.png` in the directory
+    Files { dir: &'a Path, prefix: &'a str },
+    Embedded,
+}
+
+/// Where the canvas is saved when no file was given.
+pub fn default_path() -> Option<PathBuf> {
+    let mut path = dirs::config_dir()?;
+    path.push("kajet");
+    path.set_extension("kajet");
+    Some(path)
+}
+
+/// Where unsaved changes to `canvas` are kept in case kajet doesn't close
+/// properly, each canvas gets its own file.
+pub fn recovery_path(canvas: &Path) -> Option<PathBuf> {
+    // FNV-1a, stable between runs unlike the std hasher
+    let hash = canvas
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+    let mut path = dirs::config_dir()?;
+    path.push(format!("kajet-recovery-{hash:016x}"));
+    path.set_extension("kajet");
+    Some(path)
+}
+
+/// The canvas file `path` refers to, independent of the working directory.
+pub fn canvas_key(path: &Path) -> PathBuf {
+    path::absolute(path).unwrap_or(path.to_path_buf())
+}
+
+/// The first line of a recovery file, naming the canvas it belongs to.
+pub fn recovery_header(canvas: &Path) -> String {
+    format!("# canvas {}\n", canvas.display())
+}
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let v = BASE64
+            .iter()
+            .position(|&b| b == c)
+            .ok_or("Invalid base64".to_string())?;
+        acc = acc << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+/// The image encoded as PNG.
+pub fn png_bytes(image: &texture::Image) -> Result<Vec<u8>, String> {
+    let data = image
+        .export_image_to_memory(".png")
+        .map_err(|e| e.to_string())?;
+    let bytes = data.to_vec();
+    // raylib allocated it and the binding doesn't free it
+    unsafe { ffi::MemFree(data.as_ptr() as *mut c_void) };
+    Ok(bytes)
+}
+
+/// An image read back from the GPU, it can be encoded on another thread.
+struct RawImage {
+    width: i32,
+    height: i32,
+    pixels: Vec<u8>,
+}
+
+impl RawImage {
+    fn read(texture: &Texture2D) -> Result<Self, String> {
+        let image = texture.load_image().map_err(|e| e.to_string())?;
+        let pixels = image
+            .get_image_data()
+            .iter()
+            .flat_map(|c| [c.r, c.g, c.b, c.a])
+            .collect();
+        Ok(Self {
+            width: image.width,
+            height: image.height,
+            pixels,
+        })
+    }
+
+    fn png(&mut self) -> Result<Vec<u8>, String> {
+        let image = ffi::Image {
+            data: self.pixels.as_mut_ptr() as *mut c_void,
+            width: self.width,
+            height: self.height,
+            mipmaps: 1,
+            format: ffi::PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8A8 as i32,
+        };
+        let mut size = 0;
+        // the encoder only touches the pixels, so it's fine off the main thread
+        let data = unsafe { ffi::ExportImageToMemory(image, c".png".as_ptr(), &mut size) };
+        if data.is_null() {
+            return Err("Couldn't encode an image".to_string());
+        }
+        let bytes = unsafe { slice::from_raw_parts(data, size as usize) }.to_vec();
+        unsafe { ffi::MemFree(data as *mut c_void) };
+        Ok(bytes)
+    }
+}
+
+/// The canvas in the text format with the embedded images still to be encoded.
+pub struct PendingText {
+    text: String,
+    images: Vec<(usize, CanvasPoint, f32, RawImage)>,
+}
+
+impl PendingText {
+    /// Encodes the images, the slow part of saving.
+    pub fn finish(self) -> Result<String, String> {
+        let mut out = self.text;
+        for (z, pos, scale, mut pixels) in self.images {
+            let data = base64_encode(&pixels.png()?);
+            writeln!(
+                out,
+                "image {z} {PNG_DATA}{data} {} {} {scale}",
+                pos.x, pos.y
+            )
+            .unwrap();
+        }
+        Ok(out)
+    }
+}
+
+fn color_to_text(c: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}{:02x}", c.r, c.g, c.b, c.a)
+}
+
+fn color_from_text(s: &str) -> Result<Color, String> {
+    let hex = s
+        .strip_prefix('#')
+        .filter(|h| h.len() == 8)
+        .ok_or(format!("Invalid color {s}"))?;
+    let value = u32::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
+    let [r, g, b, a] = value.to_be_bytes();
+    Ok(Color::new(r, g, b, a))
+}
+
+fn next<'a>(fields: &mut SplitWhitespace<'a>, what: &str) -> Result<&'a str, String> {
+    fields.next().ok_or(format!("Missing {what}"))
+}
+
+fn next_num<T: std::str::FromStr>(fields: &mut SplitWhitespace, what: &str) -> Result<T, String> {
+    next(fields, what)?
+        .parse::<T>()
+        .map_err(|_| format!("Invalid {what}"))
+}
+
+fn point_from_text(s: &str) -> Result<CanvasPoint, String> {
+    let (x, y) = s.split_once(',').ok_or(format!("Invalid point {s}"))?;
+    match (x.parse::<f32>(), y.parse::<f32>()) {
+        (Ok(x), Ok(y)) => Ok(CanvasPoint::new(x, y)),
+        _ => Err(format!("Invalid point {s}")),
+    }
+}
+
+impl Contents {
+    /// Writes the canvas in the text format.
+    pub fn to_text(&self, images: ImageOutput) -> Result<String, String> {
+        let ImageOutput::Files { dir, prefix } = images else {
+            return self.to_pending_text()?.finish();
+        };
+
+        let mut out = self.shapes_to_text();
+        for (i, image) in self.images.iter().enumerate() {
+            let pixels = image
+                .texture
+                .load_image()
+                .map_err(|e| format!("Couldn't read image {i} ({e})"))?;
+            let file = format!("{prefix}-{i}.png");
+            pixels.export_image(&dir.join(&file).to_string_lossy());
+            writeln!(
+                out,
+                "image {} {file} {} {} {}",
+                image.z, image.pos.x, image.pos.y, image.scale.0
+            )
+            .unwrap();
+        }
+
+        Ok(out)
+    }
+
+    /// Reads the images back from the GPU, the rest of `to_text` with embedded
+    /// images can then happen on another thread.
+    pub fn to_pending_text(&self) -> Result<PendingText, String> {
+        let images = self
+            .images
+            .iter()
+            .enumerate()
+            .map(|(i, image)| {
+                let pixels = RawImage::read(&image.texture)
+                    .map_err(|e| format!("Couldn't read image {i} ({e})"))?;
+                Ok((image.z, image.pos, image.scale.0, pixels))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(PendingText {
+            text: self.shapes_to_text(),
+            images,
+        })
+    }
+
+    /// Everything except the images in the text format.
+    fn shapes_to_text(&self) -> String {
+        let mut out = String::from("# kajet\n");
+
+        for line in &self.lines {
+            let style = if line.segmented {
+                "segmented"
+            } else {
+                "smooth"
+            };
+            let kind = match line.brush.mode {
+                BrushMode::Pen => "line",
+                BrushMode::Highlighter => "highlight",
+            };
+            write!(
+                out,
+                "{kind} {} {} {} {style}",
+                line.z(),
+                color_to_text(line.brush.color),
+                line.brush.thickness.0
+            )
+            .unwrap();
+            for p in &line.points {
+                write!(out, " {},{}", p.x, p.y).unwrap();
+            }
+            out.push('\n');
+        }
+
+        for eraser in &self.erasers {
+            let b = eraser.bounds();
+            writeln!(
+                out,
+                "eraser {} {} {} {} {} {}",
+                eraser.z(),
+                color_to_text(eraser.color()),
+                b.min.x,
+                b.min.y,
+                b.width(),
+                b.height()
+            )
+            .unwrap();
+        }
+
+        for rect in &self.rects {
+            let r = rect.rect;
+            writeln!(
+                out,
+                "rect {} {} {} {} {} {} {}",
+                rect.z(),
+                color_to_text(rect.brush.color),
+                rect.brush.thickness.0,
+                r.min.x,
+                r.min.y,
+                r.width(),
+                r.height()
+            )
+            .unwrap();
+        }
+
+        for ellipse in &self.ellipses {
+            let r = ellipse.rect;
+            writeln!(
+                out,
+                "ellipse {} {} {} {} {} {} {}",
+                ellipse.z(),
+                color_to_text(ellipse.brush.color),
+                ellipse.brush.thickness.0,
+                r.min.x,
+                r.min.y,
+                r.width(),
+                r.height()
+            )
+            .unwrap();
+        }
+
+        for arrow in &self.arrows {
+            writeln!(
+                out,
+                "arrow {} {} {} {},{} {},{}",
+                arrow.z(),
+                color_to_text(arrow.brush.color),
+                arrow.brush.thickness.0,
+                arrow.start.x,
+                arrow.start.y,
+                arrow.end.x,
+                arrow.end.y
+            )
+            .unwrap();
+        }
+
+        for text in &self.texts {
+            writeln!(
+                out,
+                "text {} {} {} {},{} {}",
+                text.z(),
+                color_to_text(text.color),
+                text.size.0,
+                text.pos.x,
+                text.pos.y,
+                text.text
+            )
+            .unwrap();
+        }
+
+        out
+    }
+
+    /// Reads a canvas written by `to_text`, image files are looked up in `dir`.
+    /// Everything gets fresh ids counting up from zero, like on a new canvas.
+    pub fn from_text(
+        text: &str,
+        dir: &Path,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        config: &Config,
+    ) -> Result<Self, String> {
+        let mut contents = Contents::new();
+
+        for (n, row) in text.lines().enumerate() {
+            let row = row.trim();
+            if row.is_empty() || row.starts_with('#') {
+                continue;
+            }
+            let at_line = |e: String| format!("{e} on line {}", n + 1);
+
+            let mut fields = row.split_whitespace();
+            let kind = next(&mut fields, "kind").map_err(at_line)?;
+            let z = next_num::<usize>(&mut fields, "z").map_err(at_line)?;
+            // the next free layer is above everything loaded
+            contents.z = contents.z.max(z + 1);
+
+            match kind {
+                "line" | "highlight" => {
+                    let color = color_from_text(next(&mut fields, "color").map_err(at_line)?)
+                        .map_err(at_line)?;
+                    let thickness = next_num::<f32>(&mut fields, "thickness").map_err(at_line)?;
+                    let segmented = match next(&mut fields, "style").map_err(at_line)? {
+                        "smooth" => false,
+                        "segmented" => true,
+                        s => return Err(at_line(format!("Invalid line style {s}"))),
+                    };
+                    let points = fields
+                        .map(point_from_text)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(at_line)?;
+                    let Some(&start) = points.first() else {
+                        return Err(at_line("Line without points".to_string()));
+                    };
+
+                    let brush = Brush {
+                        color,
+                        thickness: CanvasLength::new(thickness),
+                        mode: if kind == "highlight" {
+                            BrushMode::Highlighter
+                        } else {
+                            BrushMode::Pen
+                        },
+                    };
+                    let id = contents.next_line_id();
+                    let mut line = Line::new(start, brush, id, z);
+                    line.points = points;
+                    line.segmented = segmented;
+                    line.finished = true;
+                    contents.lines.push(line);
+                }
+                "eraser" => {
+                    let color = color_from_text(next(&mut fields, "color").map_err(at_line)?)
+                        .map_err(at_line)?;
+                    let x = next_num::<f32>(&mut fields, "x").map_err(at_line)?;
+                    let y = next_num::<f32>(&mut fields, "y").map_err(at_line)?;
+                    let w = next_num::<f32>(&mut fields, "width").map_err(at_line)?;
+                    let h = next_num::<f32>(&mut fields, "height").map_err(at_line)?;
+                    let rect = CanvasRect::new(CanvasPoint::new(x, y), CanvasSize::new(w, h));
+                    let id = contents.next_eraser_id();
+                    contents.erasers.push(Eraser::new(rect, color, id, z));
+                }
+                "rect" | "ellipse" => {
+                    let color = color_from_text(next(&mut fields, "color").map_err(at_line)?)
+                        .map_err(at_line)?;
+                    let thickness = next_num::<f32>(&mut fields, "thickness").map_err(at_line)?;
+                    let x = next_num::<f32>(&mut fields, "x").map_err(at_line)?;
+                    let y = next_num::<f32>(&mut fields, "y").map_err(at_line)?;
+                    let w = next_num::<f32>(&mut fields, "width").map_err(at_line)?;
+                    let h = next_num::<f32>(&mut fields, "height").map_err(at_line)?;
+                    let brush = Brush {
+                        color,
+                        thickness: CanvasLength::new(thickness),
+                        mode: BrushMode::Pen,
+                    };
+                    let (a, b) = (CanvasPoint::new(x, y), CanvasPoint::new(x + w, y + h));
+                    let id = contents.next_shape_id();
+                    if kind == "rect" {
+                        contents.rects.push(Rectangle::new(a, b, brush, id, z));
+                    } else {
+                        let rect = CanvasBox::from_points([a, b]);
+                        contents.ellipses.push(Ellipse::new(rect, brush, id, z));
+                    }
+                }
+                "arrow" => {
+                    let color = color_from_text(next(&mut fields, "color").map_err(at_line)?)
+                        .map_err(at_line)?;
+                    let thickness = next_num::<f32>(&mut fields, "thickness").map_err(at_line)?;
+                    let start = point_from_text(next(&mut fields, "start").map_err(at_line)?)
+                        .map_err(at_line)?;
+                    let end = point_from_text(next(&mut fields, "end").map_err(at_line)?)
+                        .map_err(at_line)?;
+                    let brush = Brush {
+                        color,
+                        thickness: CanvasLength::new(thickness),
+                        mode: BrushMode::Pen,
+                    };
+                    let id = contents.next_shape_id();
+                    contents.arrows.push(Arrow::new(start, end, brush, id, z));
+                }
+                "text" => {
+                    let color = color_from_text(next(&mut fields, "color").map_err(at_line)?)
+                        .map_err(at_line)?;
+                    let size = next_num::<f32>(&mut fields, "size").map_err(at_line)?;
+                    let pos = point_from_text(next(&mut fields, "position").map_err(at_line)?)
+                        .map_err(at_line)?;
+                    // the text keeps its spaces, so it's taken from the row as is
+                    let text = row
+                        .splitn(6, ' ')
+                        .nth(5)
+                        .ok_or("Missing text".to_string())
+                        .map_err(at_line)?;
+                    let id = contents.next_shape_id();
+                    contents.texts.push(Text::new(
+                        pos,
+                        text.to_string(),
+                        CanvasLength::new(size),
+                        color,
+                        id,
+                        z,
+                    ));
+                }
+                "image" => {
+                    let source = next(&mut fields, "image").map_err(at_line)?;
+                    let x = next_num::<f32>(&mut fields, "x").map_err(at_line)?;
+                    let y = next_num::<f32>(&mut fields, "y").map_err(at_line)?;
+                    let scale = next_num::<f32>(&mut fields, "scale").map_err(at_line)?;
+
+                    let mut texture: Texture2D = if let Some(data) = source.strip_prefix(PNG_DATA) {
+                        let bytes = base64_decode(data).map_err(at_line)?;
+                        let image = texture::Image::load_image_from_mem(".png", &bytes)
+                            .map_err(|e| at_line(format!("Couldn't decode an image ({e})")))?;
+                        rl.load_texture_from_image(thread, &image)
+                            .map_err(|e| at_line(e.to_string()))?
+                    } else {
+                        rl.load_texture(thread, &dir.join(source).to_string_lossy())
+                            .map_err(|e| at_line(format!("Couldn't load {source} ({e})")))?
+                    };
+                    configure_texture(&mut texture, thread, config);
+                    let id = contents.next_image_id();
+                    contents.images.push(Image::new(
+                        CanvasPoint::new(x, y),
+                        texture,
+                        CanvasLength::new(config.clamp_image_scale(scale)),
+                        id,
+                        z,
+                        config,
+                    ));
+                }
+                _ => return Err(at_line(format!("Unknown primitive {kind}"))),
+            }
+        }
+
+        Ok(contents)
+    }
+}
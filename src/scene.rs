@@ -1,19 +1,34 @@
 use std::{
+    fs, mem,
     ops::{Add, Mul},
+    path::{Path, PathBuf},
     rc::Rc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use raylib::{RaylibHandle, RaylibThread, camera};
+use raylib::{
+    RaylibHandle, RaylibThread, camera,
+    color::Color,
+    texture::{self, Texture2D},
+};
 use widok::{
-    Camera, CanvasLength, CanvasSize, CanvasSpace, CanvasToScreenScale, ScreenPoint, ToCanvas,
+    Bounds, Camera, CanvasLength, CanvasPoint, CanvasSize, CanvasSpace, CanvasToScreenScale,
+    InView, ScreenPoint, ScreenSize, ScreenVector, ToCanvas, ToScreen,
 };
 
 use crate::{
     clipboard::Clipboard,
-    command::CommandInvoker,
+    command::{self, CommandInvoker, Timeline},
     config::Config,
-    graphics::{Brush, Contents, Drawable, ImageId},
+    export::{self, ExportArea, ExportView},
+    graphics::{
+        Brush, BrushMode, Contents, Drawable, Image, ImageId, Notification, ScreenNote, Snapshot,
+        configure_texture,
+    },
     input::InputHandler,
+    save::{self, ImageOutput},
+    selection::Selection,
     state::{self, StateHandler, Transition},
 };
 
@@ -23,25 +38,199 @@ pub struct Scene {
 }
 
 impl Scene {
-    pub fn new(config: Config, clipboard: Option<Clipboard>, rl: &mut RaylibHandle) -> Self {
+    pub fn new(
+        config: Config,
+        clipboard: Option<Clipboard>,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+    ) -> Self {
         let mut sm = Self {
             state: Box::new(state::Idle),
             data: SceneData::new(config, clipboard),
         };
+        sm.data.ui_scale = sm
+            .data
+            .config
+            .ui_scale
+            .unwrap_or_else(|| rl.get_window_scale_dpi().x.max(1.0));
+        if let Some(path) = sm.data.config.tracing_image.clone() {
+            match rl.load_texture(thread, &path) {
+                Ok(texture) => sm
+                    .data
+                    .set_tracing(texture, CanvasPoint::origin(), 1.0, thread),
+                Err(e) => eprintln!("[ERROR] Couldn't load the tracing image {path} ({e})"),
+            }
+        }
 
         sm.state.on_enter(&mut sm.data, rl);
         sm
     }
 
+    /// Where to read the config from when reloading it, `None` if it can't be.
+    pub fn set_config_path(&mut self, path: Option<Option<String>>) {
+        self.data.config_path = path;
+    }
+
+    /// Loads the canvas from `path` if there's one, otherwise starts
+    /// a new canvas that gets saved there.
+    pub fn open(&mut self, path: PathBuf, rl: &mut RaylibHandle, thread: &RaylibThread) {
+        if !path.exists() {
+            eprintln!("[INFO] {} will be created on save", path.display());
+            self.data.canvas_path = Some(path);
+            return;
+        }
+        if let Err(e) = self.data.load(path, rl, thread) {
+            eprintln!("[ERROR] Couldn't load the canvas: {e}");
+        }
+    }
+
+    /// Opens the image files on the canvas once the window is up.
+    pub fn open_images(&mut self, paths: Vec<String>) {
+        self.data.pending_images.extend(paths);
+    }
+
+    /// Loads the recovery file if it's newer than the opened canvas.
+    pub fn recover(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
+        let Some((canvas, path)) = self.data.recovery_file() else {
+            return;
+        };
+        let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+        let Some(recovered) = modified(&path) else {
+            return;
+        };
+        if let Some(saved) = modified(&canvas)
+            && saved >= recovered
+        {
+            return;
+        }
+        // never put one canvas's changes into another
+        let header = save::recovery_header(&canvas);
+        match fs::read_to_string(&path) {
+            Ok(text) if text.starts_with(&header) => {}
+            _ => {
+                eprintln!(
+                    "[ERROR] {} doesn't belong to {}, not recovering",
+                    path.display(),
+                    canvas.display()
+                );
+                return;
+            }
+        }
+
+        match self.data.read_contents(&path, rl, thread) {
+            Ok(()) => {
+                eprintln!("[INFO] Recovered unsaved changes from {}", path.display());
+                self.data.notify("Recovered unsaved changes".to_string());
+                // so they aren't lost again by quitting
+                self.data.command_invoker.mark_unsaved();
+            }
+            Err(e) => eprintln!("[ERROR] Couldn't recover from {} ({e})", path.display()),
+        }
+    }
+
+    /// Cleans up after a proper exit.
+    pub fn close(&mut self) {
+        self.data.finish_autosave();
+        if let Some((_, path)) = self.data.recovery_file() {
+            remove_recovery(&path);
+        }
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.data.quit
+    }
+
+    fn switch(&mut self, mut next: Box<dyn StateHandler>, rl: &mut RaylibHandle) {
+        // only letting go of the canvas starts a glide, anything else ends it
+        self.data.pan_velocity = ScreenVector::zero();
+        self.data.held_delta = ScreenVector::zero();
+        self.state.on_exit(&mut self.data, rl);
+        next.on_enter(&mut self.data, rl);
+        self.state = next;
+    }
+
     pub fn process_frame(&mut self, thread: &RaylibThread, rl: &mut RaylibHandle) {
-        if let Transition::Switch(mut next) = self.state.step(&mut self.data, thread, rl) {
-            self.state.on_exit(&mut self.data, rl);
-            next.on_enter(&mut self.data, rl);
-            self.state = next;
+        // raylib resets the close request every frame so it can be ignored
+        if rl.window_should_close() {
+            // closing again while being asked means it
+            if self.data.command_invoker.is_dirty() && !self.data.confirming_quit {
+                self.switch(Box::new(state::ConfirmQuit::new(&self.data, rl)), rl);
+            } else {
+                self.data.quit = true;
+                return;
+            }
+        }
+
+        if let Transition::Switch(next) = self.state.step(&mut self.data, thread, rl) {
+            self.switch(next, rl);
         }
 
+        if !self.data.playing_back {
+            self.data
+                .timeline
+                .record(&self.data.contents, self.data.command_invoker.changes());
+        }
+        self.data.autosave(rl.get_time());
+        // counting what's in view goes through everything, so only when shown
+        if self.data.config.show_fps {
+            self.data
+                .stats
+                .update(rl.get_frame_time(), &self.data.contents, &self.data.camera);
+        }
         self.state.draw(&mut self.data, thread, rl);
         self.data.contents.overlay.clear();
+
+        // the camera knows the window size only after the first frame
+        for path in mem::take(&mut self.data.pending_images) {
+            let center = SceneData::screen_center(rl);
+            if let Err(e) = self.data.open_image(&path, center, rl, thread) {
+                eprintln!("[ERROR] {e}");
+            }
+        }
+    }
+}
+
+fn remove_recovery(path: &Path) {
+    if path.exists()
+        && let Err(e) = fs::remove_file(path)
+    {
+        eprintln!("[ERROR] Couldn't remove {} ({e})", path.display());
+    }
+}
+
+/// Numbers worth looking at when something is slow.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    // moving average in seconds
+    pub frame_time: f32,
+    pub drawables: usize,
+    pub visible: usize,
+    pub textures: usize,
+}
+
+impl Stats {
+    fn update(&mut self, frame_time: f32, contents: &Contents, camera: &Camera) {
+        const SMOOTHING: f32 = 0.05;
+
+        self.frame_time += (frame_time - self.frame_time) * SMOOTHING;
+        self.drawables = 0;
+        self.visible = 0;
+        for i in contents.unsorted_drawables() {
+            self.drawables += 1;
+            if i.is_in_view(camera) {
+                self.visible += 1;
+            }
+        }
+
+        // duplicated images share their texture
+        let mut textures = contents
+            .images
+            .iter()
+            .map(|i| Rc::as_ptr(&i.texture))
+            .collect::<Vec<_>>();
+        textures.sort();
+        textures.dedup();
+        self.textures = textures.len();
     }
 }
 
@@ -52,8 +241,60 @@ pub struct SceneData {
     pub command_invoker: CommandInvoker,
     pub brush: Brush<CanvasSpace>,
     pub config: Rc<Config>,
+    // the config path to reload from (None looks it up like on startup),
+    // not set if the config was given inline or through stdin
+    pub config_path: Option<Option<String>>,
     pub clipboard: Option<Clipboard>,
+    pub notes: Vec<ScreenNote>,
+    pub selection: Selection,
+    // drawables copied within kajet, separate from the system clipboard
+    pub copied: Snapshot,
+    // the clipboard image when the drawables were copied, pasting prefers
+    // them until the clipboard changes
+    #[cfg(not(target_arch = "wasm32"))]
+    pub copied_over: Option<u64>,
+    pub stats: Stats,
+    pub notification: Option<Notification>,
+    pub quit: bool,
+    pub confirming_quit: bool,
+    pub show_history: bool,
+    // exported when the next frame gets drawn
+    pub export: Option<ExportArea>,
+    // copied to the clipboard as an image when the next frame gets drawn
+    pub copy_image: Option<Selection>,
+    // the .kajet file the canvas was loaded from or last saved to
+    pub canvas_path: Option<PathBuf>,
+    // image files to open once the window is up
+    pub pending_images: Vec<String>,
+    // when the last autosave happened (in seconds) and what it saved
+    autosaved_at: f64,
+    autosaved_changes: usize,
+    // when the number of changes last went up, for `autosave_debounce`
+    changed_at: f64,
+    seen_changes: usize,
+    // the autosave being written, returns how many changes it covers
+    autosaving: Option<JoinHandle<Result<usize, String>>>,
+    // what playback replays
+    pub timeline: Timeline,
+    // the real contents are put aside while the history plays back
+    pub playing_back: bool,
+    pub checkpoints: Vec<Snapshot>,
+    // the checkpoint restored last, the next one restored is the one before it
+    pub checkpoint_idx: Option<usize>,
+    // where the mouse was on the previous frame
+    pub last_mouse: Option<ScreenPoint>,
+    // whether the view is on its way back to the canvas origin
+    pub resetting_view: bool,
+    // how fast (in pixels per second) the camera keeps moving after panning
+    pub pan_velocity: ScreenVector,
+    // mouse movement within the pan deadzone, see `steady_mouse_delta`
+    pub held_delta: ScreenVector,
+    // how much bigger the interface is drawn
+    pub ui_scale: f32,
+    // the colors to cycle through, starts out as the configured ones
+    palette: Vec<Color>,
     color_idx: usize,
+    color_changed: Option<Instant>,
 }
 
 impl SceneData {
@@ -62,47 +303,564 @@ impl SceneData {
         let brush = Brush {
             color: config.colors[0],
             thickness: CanvasLength::new(config.thickness),
+            mode: BrushMode::Pen,
         };
-        let command_invoker = CommandInvoker::new(config.undo_buffer_size);
+        let command_invoker = CommandInvoker::new(
+            config.undo_buffer_size,
+            Duration::from_millis(config.undo_group_time.into()),
+        );
         let input_handler = InputHandler::new(Rc::clone(&config));
         let camera = Camera::new(CanvasSize::new(0.0, 0.0));
 
         Self {
             camera,
             config,
+            config_path: Some(None),
             color_idx: 0,
+            color_changed: None,
             brush,
             contents: Contents::new(),
             clipboard,
+            notes: vec![],
+            selection: Selection::default(),
+            copied: Snapshot::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            copied_over: None,
+            stats: Stats::default(),
+            notification: None,
+            quit: false,
+            confirming_quit: false,
+            show_history: false,
+            export: None,
+            copy_image: None,
+            canvas_path: None,
+            pending_images: vec![],
+            autosaved_at: 0.0,
+            autosaved_changes: 0,
+            changed_at: 0.0,
+            seen_changes: 0,
+            autosaving: None,
+            timeline: Timeline::new(&Contents::new(), 0),
+            playing_back: false,
+            checkpoints: vec![],
+            checkpoint_idx: None,
+            last_mouse: None,
+            resetting_view: false,
+            pan_velocity: ScreenVector::zero(),
+            held_delta: ScreenVector::zero(),
+            ui_scale: 1.0,
+            palette: config.colors.to_vec(),
             command_invoker,
             input_handler,
         }
     }
 
+    fn scroll_steps(&self, scroll_y: f32) -> f32 {
+        // limit how much a single frame can change so fast trackpad
+        // gestures don't jump straight to the clamps
+        const MAX_STEPS: f32 = 5.0;
+
+        let direction = if self.config.invert_scroll { -1.0 } else { 1.0 };
+        (direction * scroll_y * self.config.scroll_sensitivity).clamp(-MAX_STEPS, MAX_STEPS)
+    }
+
     pub fn update_thickness(&mut self, scroll_y: f32) {
         self.brush.thickness = self
             .brush
             .thickness
-            .add(CanvasLength::new(scroll_y.signum()))
+            .add(CanvasLength::new(self.scroll_steps(scroll_y)))
             .min(CanvasLength::new(30.0))
             .max(CanvasLength::new(1.0));
     }
 
-    pub fn update_zoom(&mut self, scroll_y: f32) {
+    pub fn stroke_brush(&self) -> Brush<CanvasSpace> {
+        let mut brush = self.brush;
+        if self.config.snap_thickness {
+            brush.thickness = CanvasLength::new(brush.thickness.0.round().max(1.0));
+        }
+        brush
+    }
+
+    /// The range the zoom is clamped to.
+    fn zoom_limits(&self, rl: &RaylibHandle) -> (f32, f32) {
+        let (min_zoom, max_zoom) = self.config.zoom;
+        let screen = ScreenSize::new(rl.get_screen_width() as f32, rl.get_screen_height() as f32);
+        if !self.config.adaptive_zoom || screen.is_empty() {
+            return (min_zoom, max_zoom);
+        }
+        let Some(bounds) = self.contents.bounding_box() else {
+            return (min_zoom, max_zoom);
+        };
+
+        let size = bounds.size();
+        // everything fits in half of the window
+        let min =
+            (screen.width / size.width.max(1.0)).min(screen.height / size.height.max(1.0)) / 2.0;
+        let smallest = self
+            .contents
+            .lines
+            .iter()
+            .map(|l| l.bounds())
+            .chain(self.contents.images.iter().map(|i| i.bounds()))
+            .chain(self.contents.rects.iter().map(|r| r.bounds()))
+            .chain(self.contents.ellipses.iter().map(|e| e.bounds()))
+            .chain(self.contents.arrows.iter().map(|a| a.bounds()))
+            .chain(self.contents.texts.iter().map(|t| t.bounds()))
+            .map(|b| b.size().width.max(b.size().height))
+            .fold(f32::INFINITY, f32::min);
+        // erasers alone don't tell how detailed the drawing is
+        if !smallest.is_finite() {
+            return (min, max_zoom.max(min));
+        }
+        let max = screen.width.min(screen.height) / smallest.max(1.0);
+
+        // a wide window can want to zoom out further than in
+        (min, max.max(min))
+    }
+
+    pub fn update_zoom(&mut self, rl: &RaylibHandle, scroll_y: f32) {
+        let (min, max) = self.zoom_limits(rl);
         let new_zoom = self
             .camera
             .zoom()
             .0
-            .mul(1.0 + scroll_y.signum() * 0.2 * self.config.scroll_sensitivity)
-            .clamp(0.1, 30.0);
+            .mul(1.2f32.powf(self.scroll_steps(scroll_y)))
+            .clamp(min, max);
         self.camera.update_zoom(CanvasToScreenScale::new(new_zoom));
+        self.resetting_view = false;
+        self.pan_velocity = ScreenVector::zero();
+    }
+
+    pub fn reset_view(&mut self) {
+        self.camera.update_zoom(CanvasToScreenScale::new(1.0));
+        self.resetting_view = true;
+        self.pan_velocity = ScreenVector::zero();
+    }
+
+    /// Moves the camera by what's left of the panning momentum.
+    pub fn step_momentum(&mut self, dt: f32) {
+        // how quickly the camera slows down
+        const DECAY: f32 = 5.0;
+        // below this speed (in pixels per second) the camera stops
+        const MIN_SPEED: f32 = 10.0;
+
+        if self.pan_velocity.length() < MIN_SPEED {
+            self.pan_velocity = ScreenVector::zero();
+            return;
+        }
+        self.camera.update_pos(self.pan_velocity * dt);
+        self.pan_velocity *= (-DECAY * dt).exp();
+    }
+
+    /// Moves the view a step closer to the canvas origin at the top left
+    /// corner, the zoom is interpolated by the camera.
+    pub fn step_reset_view(&mut self, dt: f32) {
+        // how quickly the remaining distance shrinks
+        const SPEED: f32 = 12.0;
+
+        let origin = CanvasPoint::origin().to_screen(&self.camera).to_vector();
+        let t = (1.0 - (-SPEED * dt).exp()).min(1.0);
+        self.camera.update_pos(-origin * t);
+
+        if origin.length() < 0.5 && (self.camera.zoom().0 - 1.0).abs() < 1e-3 {
+            self.resetting_view = false;
+        }
     }
 
     pub fn update_color(&mut self, forward: bool) {
         self.color_idx = (self.color_idx as i32 + if forward { 1 } else { -1 })
-            .rem_euclid(self.config.colors.len() as i32) as usize;
+            .rem_euclid(self.palette.len() as i32) as usize;
+
+        self.brush.color = self.palette[self.color_idx];
+        self.color_changed = Some(Instant::now());
+        // a different pen starts a new undo group
+        self.command_invoker.end_group();
+    }
+
+    /// Picks the `idx`-th color, does nothing if there are fewer colors.
+    pub fn select_color(&mut self, idx: usize) {
+        if idx >= self.palette.len() {
+            return;
+        }
+        self.color_idx = idx;
+        self.brush.color = self.palette[idx];
+        self.color_changed = Some(Instant::now());
+        self.command_invoker.end_group();
+    }
+
+    pub fn color_idx(&self) -> usize {
+        self.color_idx
+    }
+
+    pub fn palette(&self) -> &[Color] {
+        &self.palette
+    }
+
+    /// Replaces the colors to cycle through and picks the first one.
+    pub fn set_palette(&mut self, colors: Vec<Color>) {
+        if colors.is_empty() {
+            return;
+        }
+        self.palette = colors;
+        self.color_idx = 0;
+        self.brush.color = self.palette[0];
+        self.color_changed = Some(Instant::now());
+        self.command_invoker.end_group();
+    }
+
+    pub fn since_color_change(&self) -> Option<Duration> {
+        self.color_changed.map(|t| t.elapsed())
+    }
+
+    pub fn snapping(&self, rl: &RaylibHandle) -> bool {
+        !self.config.disable_snap.is_down(rl)
+    }
+
+    /// Rounds `p` to the snap spacing unless snapping is held off.
+    pub fn snap_to_grid(&self, rl: &RaylibHandle, p: CanvasPoint) -> CanvasPoint {
+        let spacing = self.config.snap_spacing;
+        if spacing == 0.0 || !self.snapping(rl) {
+            return p;
+        }
+
+        (p / spacing).round() * spacing
+    }
+
+    pub fn stroke_finished(&mut self) {
+        if self.config.auto_advance_color {
+            self.update_color(true);
+        }
+    }
+
+    /// Turns `image` into a texture no larger than `max_image_size`,
+    /// returns it along with how much it was downscaled.
+    pub fn load_scaled_texture(
+        &self,
+        mut image: texture::Image,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+    ) -> Option<(Texture2D, f32)> {
+        // large textures can exceed GPU limits and slow everything down
+        let max_size = self.config.max_image_size as i32;
+        let (width, height) = (image.width, image.height);
+        let mut scale = 1.0;
+        if width.max(height) > max_size {
+            scale = width.max(height) as f32 / max_size as f32;
+            let new_width = ((width as f32 / scale) as i32).max(1);
+            let new_height = ((height as f32 / scale) as i32).max(1);
+            image.resize(new_width, new_height);
+            eprintln!(
+                "[INFO] Downscaled the image from {width}x{height} to {new_width}x{new_height}."
+            );
+        }
+
+        let texture = rl.load_texture_from_image(thread, &image).ok()?;
+        Some((texture, scale))
+    }
+
+    /// Puts `texture` on top of the canvas centered at `center`,
+    /// `scale` is how much it was downscaled.
+    pub fn add_image(
+        &mut self,
+        mut texture: Texture2D,
+        scale: f32,
+        center: ScreenPoint,
+        thread: &RaylibThread,
+    ) {
+        configure_texture(&mut texture, thread, &self.config);
+        let delta = ScreenVector::new(
+            texture.width as f32 * scale / 2.0,
+            texture.height as f32 * scale / 2.0,
+        );
+
+        // TODO: consider adding this to contents instead of exposing this api
+        self.contents.z += 1;
+        let image = Image::new(
+            (center - delta).to_canvas(&self.camera),
+            texture,
+            // keep the size it would have without downscaling
+            CanvasLength::new(self.config.clamp_image_scale(scale / self.camera.zoom().0)),
+            self.contents.next_image_id(),
+            self.contents.z,
+            &self.config,
+        );
+        self.contents.images.push(image.clone());
+        self.command_invoker.push(command::PasteImage::new(image));
+    }
+
+    /// Loads an image file onto the canvas centered at `center`.
+    pub fn open_image(
+        &mut self,
+        path: &str,
+        center: ScreenPoint,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+    ) -> Result<(), String> {
+        let image =
+            texture::Image::load_image(path).map_err(|e| format!("Couldn't open {path} ({e})"))?;
+        let (texture, scale) = self
+            .load_scaled_texture(image, rl, thread)
+            .ok_or(format!("Couldn't load {path}"))?;
+        self.add_image(texture, scale, center, thread);
+        Ok(())
+    }
+
+    /// The middle of the window.
+    pub fn screen_center(rl: &RaylibHandle) -> ScreenPoint {
+        ScreenPoint::new(
+            rl.get_screen_width() as f32 / 2.0,
+            rl.get_screen_height() as f32 / 2.0,
+        )
+    }
+
+    /// Puts `texture` under the drawing to trace over.
+    pub fn set_tracing(
+        &mut self,
+        mut texture: Texture2D,
+        pos: CanvasPoint,
+        scale: f32,
+        thread: &RaylibThread,
+    ) {
+        configure_texture(&mut texture, thread, &self.config);
+        let mut image = Image::new(
+            pos,
+            texture,
+            CanvasLength::new(scale),
+            self.contents.next_image_id(),
+            0,
+            &self.config,
+        );
+        image.opacity = self.config.tracing_opacity;
+        self.contents.tracing = Some(image);
+        self.contents.show_tracing = true;
+    }
+
+    /// Writes the canvas to the configured text file.
+    pub fn save_text(&mut self) -> Result<(), String> {
+        let path = Path::new(&self.config.text_file);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let prefix = path
+            .file_stem()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default();
+        let text = self.contents.to_text(ImageOutput::Files {
+            dir,
+            prefix: &prefix,
+        })?;
+        fs::write(path, text).map_err(|e| format!("Couldn't write {} ({e})", path.display()))?;
+        self.command_invoker.mark_saved();
+        Ok(())
+    }
+
+    /// Writes the canvas along with its images into a single file.
+    pub fn save(&mut self) -> Result<PathBuf, String> {
+        let path = self
+            .canvas_path
+            .clone()
+            .or_else(save::default_path)
+            .ok_or("Nowhere to save the canvas".to_string())?;
+        let text = self.contents.to_text(ImageOutput::Embedded)?;
+        fs::write(&path, text).map_err(|e| format!("Couldn't write {} ({e})", path.display()))?;
+        // an autosave finishing later would bring the recovery file back
+        self.finish_autosave();
+        if let Some((_, recovery)) = self.recovery_file() {
+            remove_recovery(&recovery);
+        }
+        self.canvas_path = Some(path.clone());
+        self.command_invoker.mark_saved();
+        Ok(path)
+    }
+
+    /// The canvas saves go to and the recovery file for it.
+    fn recovery_file(&self) -> Option<(PathBuf, PathBuf)> {
+        let canvas = save::canvas_key(&self.canvas_path.clone().or_else(save::default_path)?);
+        let recovery = save::recovery_path(&canvas)?;
+        Some((canvas, recovery))
+    }
+
+    /// Waits for the autosave being written, if any.
+    fn finish_autosave(&mut self) {
+        if let Some(handle) = self.autosaving.take() {
+            self.autosaved(handle);
+        }
+    }
+
+    fn autosaved(&mut self, handle: JoinHandle<Result<usize, String>>) {
+        match handle.join() {
+            Ok(Ok(changes)) => self.autosaved_changes = changes,
+            Ok(Err(e)) => eprintln!("[ERROR] Couldn't autosave ({e})"),
+            Err(_) => eprintln!("[ERROR] Autosaving crashed"),
+        }
+    }
+
+    /// Writes the changes to the recovery file every `autosave_interval` seconds
+    /// and `autosave_debounce` milliseconds after the last change, the images
+    /// get encoded on another thread so drawing doesn't stall.
+    fn autosave(&mut self, now: f64) {
+        if let Some(handle) = self.autosaving.take_if(|h| h.is_finished()) {
+            self.autosaved(handle);
+        }
+        let changes = self.command_invoker.changes();
+        if changes != self.seen_changes {
+            self.seen_changes = changes;
+            self.changed_at = now;
+        }
+
+        let interval = self.config.autosave_interval as f64;
+        let debounce = self.config.autosave_debounce as f64 / 1000.0;
+        let periodic = interval > 0.0 && now - self.autosaved_at >= interval;
+        // a failed write gets retried no sooner than the debounce either
+        let settled = debounce > 0.0
+            && changes != self.autosaved_changes
+            && now - self.changed_at >= debounce
+            && now - self.autosaved_at >= debounce;
+        if !(periodic || settled)
+            || self.autosaving.is_some()
+            // the canvas is only partially replayed
+            || self.playing_back
+        {
+            return;
+        }
+        self.autosaved_at = now;
+
+        if !self.command_invoker.is_dirty() || changes == self.autosaved_changes {
+            return;
+        }
+        let Some((canvas, path)) = self.recovery_file() else {
+            return;
+        };
+        let pending = match self.contents.to_pending_text() {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("[ERROR] Couldn't autosave to {} ({e})", path.display());
+                return;
+            }
+        };
+        self.autosaving = Some(thread::spawn(move || {
+            let text = pending.finish()?;
+            let text = save::recovery_header(&canvas) + &text;
+            fs::write(&path, text).map_err(|e| format!("{} ({e})", path.display()))?;
+            Ok(changes)
+        }));
+    }
+
+    pub fn export_view(&self) -> ExportView<'_> {
+        ExportView {
+            camera: &self.camera,
+            background: self.config.background,
+            transparent: self.config.transparent_background,
+        }
+    }
+
+    /// Writes the canvas, or only `selection` if given, as SVG next to the
+    /// configured export file.
+    pub fn export_svg(&self, selection: Option<&Selection>) -> Result<PathBuf, String> {
+        let path = Path::new(&self.config.export_file).with_extension("svg");
+        let svg = export::export_svg(
+            &self.contents,
+            selection,
+            (!self.config.transparent_background).then_some(self.config.background),
+        )?;
+        fs::write(&path, svg).map_err(|e| format!("Couldn't write {} ({e})", path.display()))?;
+        Ok(path)
+    }
+
+    /// Replaces the canvas with the one saved in `path`, later saves go there too.
+    pub fn load(
+        &mut self,
+        path: PathBuf,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+    ) -> Result<(), String> {
+        self.read_contents(&path, rl, thread)?;
+        self.canvas_path = Some(path);
+        Ok(())
+    }
+
+    /// Reads the config again and applies it, the canvas, history and brush
+    /// thickness stay.
+    pub fn reload_config(&mut self, rl: &mut RaylibHandle) -> Result<(), String> {
+        let Some(path) = self.config_path.clone() else {
+            return Err("The config wasn't read from a file".to_string());
+        };
+        let config = Rc::new(Config::reload(path)?);
+
+        rl.set_target_fps(config.fps);
+        rl.set_exit_key(config.exit_key);
+        self.ui_scale = config
+            .ui_scale
+            .unwrap_or_else(|| rl.get_window_scale_dpi().x.max(1.0));
+        self.palette = config.colors.to_vec();
+        self.color_idx = self.color_idx.min(self.palette.len() - 1);
+        self.brush.color = self.palette[self.color_idx];
+        self.command_invoker.set_limits(
+            config.undo_buffer_size,
+            Duration::from_millis(config.undo_group_time.into()),
+        );
+        self.input_handler = InputHandler::new(Rc::clone(&config));
+        self.config = config;
+        Ok(())
+    }
+
+    /// Replaces the canvas with the one in the configured text file.
+    pub fn load_text(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+    ) -> Result<(), String> {
+        let path = PathBuf::from(&self.config.text_file);
+        self.read_contents(&path, rl, thread)
+    }
+
+    fn read_contents(
+        &mut self,
+        path: &Path,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+    ) -> Result<(), String> {
+        let config = Rc::clone(&self.config);
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {} ({e})", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut contents = Contents::from_text(&text, dir, rl, thread, &config)?;
+
+        contents.tracing = self.contents.tracing.take();
+        contents.show_tracing = self.contents.show_tracing;
+        self.contents = contents;
+        self.selection = Selection::default();
+        self.command_invoker = CommandInvoker::new(
+            config.undo_buffer_size,
+            Duration::from_millis(config.undo_group_time.into()),
+        );
+        self.timeline = Timeline::new(&self.contents, self.command_invoker.changes());
+        Ok(())
+    }
+
+    pub fn notify(&mut self, text: String) {
+        self.notification = Some(Notification::new(text));
+    }
+
+    /// Describes how big the canvas is and how long the last frame took.
+    pub fn canvas_summary(&self, frame_time: f32) -> String {
+        let contents = &self.contents;
+        let points = contents.lines.iter().map(|l| l.points.len()).sum::<usize>();
+        let extent = contents
+            .bounding_box()
+            .map_or(CanvasSize::zero(), |b| b.size());
 
-        self.brush.color = self.config.colors[self.color_idx];
+        format!(
+            "{} lines ({points} points), {} images, {} erasers, {} rectangles, {} ellipses, {} arrows, {} texts, {:.0}x{:.0} units, {:.2} ms",
+            contents.lines.len(),
+            contents.images.len(),
+            contents.erasers.len(),
+            contents.rects.len(),
+            contents.ellipses.len(),
+            contents.arrows.len(),
+            contents.texts.len(),
+            extent.width,
+            extent.height,
+            frame_time * 1000.0,
+        )
     }
 
     pub fn image_under_cursor(&self, mouse: ScreenPoint) -> Option<ImageId> {
@@ -0,0 +1,417 @@
+use raylib::{
+    color::Color,
+    math::Vector2,
+    prelude::{RaylibDraw, RaylibDrawHandle},
+};
+use widok::{Bounds, Camera, CanvasBox, CanvasPoint, CanvasVector, InView, ToScreen};
+
+use crate::graphics::{
+    self, Contents, Drawable, EraserId, ImageId, Line, LineId, ShapeId, Snapshot,
+};
+
+/// Drawables picked by the user to be operated on together.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    pub lines: Vec<LineId>,
+    pub images: Vec<ImageId>,
+    pub erasers: Vec<EraserId>,
+    // rectangles, arrows and text
+    pub shapes: Vec<ShapeId>,
+}
+
+impl Selection {
+    /// Everything whose bounds lie entirely within `area`.
+    pub fn within(area: CanvasBox, contents: &Contents) -> Self {
+        Self::matching(
+            contents,
+            |l| area.contains_box(&l.bounds()),
+            |b| area.contains_box(&b),
+        )
+    }
+
+    /// Everything enclosed by `lasso`, lines by their points and the rest by
+    /// their bounds.
+    pub fn lassoed(lasso: &Lasso, contents: &Contents) -> Self {
+        Self::matching(contents, |l| lasso.encloses(l), |b| lasso.encloses_box(b))
+    }
+
+    fn matching(
+        contents: &Contents,
+        line: impl Fn(&Line) -> bool,
+        inside: impl Fn(CanvasBox) -> bool,
+    ) -> Self {
+        Self {
+            lines: contents
+                .lines
+                .iter()
+                .filter(|l| line(l))
+                .map(|l| l.id)
+                .collect(),
+            images: contents
+                .images
+                .iter()
+                .filter(|i| inside(i.bounds()))
+                .map(|i| i.id)
+                .collect(),
+            erasers: contents
+                .erasers
+                .iter()
+                .filter(|e| inside(e.bounds()))
+                .map(|e| e.id)
+                .collect(),
+            shapes: contents
+                .rects
+                .iter()
+                .filter(|r| inside(r.bounds()))
+                .map(|r| r.id)
+                .chain(
+                    contents
+                        .ellipses
+                        .iter()
+                        .filter(|e| inside(e.bounds()))
+                        .map(|e| e.id),
+                )
+                .chain(
+                    contents
+                        .arrows
+                        .iter()
+                        .filter(|a| inside(a.bounds()))
+                        .map(|a| a.id),
+                )
+                .chain(
+                    contents
+                        .texts
+                        .iter()
+                        .filter(|t| inside(t.bounds()))
+                        .map(|t| t.id),
+                )
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+            && self.images.is_empty()
+            && self.erasers.is_empty()
+            && self.shapes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.images.clear();
+        self.erasers.clear();
+        self.shapes.clear();
+    }
+
+    /// Shows or hides the border around the selected images.
+    pub fn mark(&self, contents: &mut Contents, selected: bool) {
+        for &id in &self.images {
+            if let Some(img) = contents.image(id) {
+                img.is_selected = selected;
+            }
+        }
+    }
+
+    pub fn translate(&self, contents: &mut Contents, offset: CanvasVector) {
+        for &id in &self.lines {
+            if let Some(line) = contents.line(id) {
+                line.translate(offset);
+            }
+        }
+        for &id in &self.images {
+            if let Some(img) = contents.image(id) {
+                img.pos += offset;
+            }
+        }
+        for &id in &self.erasers {
+            if let Some(eraser) = contents.eraser(id) {
+                eraser.translate(offset);
+            }
+        }
+        let shape = |id| self.shapes.contains(&id);
+        contents
+            .rects
+            .iter_mut()
+            .filter(|r| shape(r.id))
+            .for_each(|r| r.translate(offset));
+        contents
+            .ellipses
+            .iter_mut()
+            .filter(|e| shape(e.id))
+            .for_each(|e| e.translate(offset));
+        contents
+            .arrows
+            .iter_mut()
+            .filter(|a| shape(a.id))
+            .for_each(|a| a.translate(offset));
+        contents
+            .texts
+            .iter_mut()
+            .filter(|t| shape(t.id))
+            .for_each(|t| t.translate(offset));
+    }
+
+    /// The selected drawables sorted by z.
+    pub fn drawables<'a>(&self, contents: &'a Contents) -> Vec<&'a dyn Drawable> {
+        let lines = contents
+            .lines
+            .iter()
+            .filter(|l| self.lines.contains(&l.id))
+            .map(|l| l as &dyn Drawable);
+        let images = contents
+            .images
+            .iter()
+            .filter(|i| self.images.contains(&i.id))
+            .map(|i| i as &dyn Drawable);
+        let erasers = contents
+            .erasers
+            .iter()
+            .filter(|e| self.erasers.contains(&e.id))
+            .map(|e| e as &dyn Drawable);
+        let rects = contents
+            .rects
+            .iter()
+            .filter(|r| self.shapes.contains(&r.id))
+            .map(|r| r as &dyn Drawable);
+        let ellipses = contents
+            .ellipses
+            .iter()
+            .filter(|e| self.shapes.contains(&e.id))
+            .map(|e| e as &dyn Drawable);
+        let arrows = contents
+            .arrows
+            .iter()
+            .filter(|a| self.shapes.contains(&a.id))
+            .map(|a| a as &dyn Drawable);
+        let texts = contents
+            .texts
+            .iter()
+            .filter(|t| self.shapes.contains(&t.id))
+            .map(|t| t as &dyn Drawable);
+
+        let mut drawables = lines
+            .chain(images)
+            .chain(erasers)
+            .chain(rects)
+            .chain(ellipses)
+            .chain(arrows)
+            .chain(texts)
+            .collect::<Vec<_>>();
+        drawables.sort_by_key(|i| i.z());
+        drawables
+    }
+
+    /// Copies of the selected drawables, with images unmarked.
+    pub fn copy(&self, contents: &Contents) -> Snapshot {
+        Snapshot {
+            lines: contents
+                .lines
+                .iter()
+                .filter(|l| self.lines.contains(&l.id))
+                .cloned()
+                .collect(),
+            images: contents
+                .images
+                .iter()
+                .filter(|i| self.images.contains(&i.id))
+                .map(|i| {
+                    let mut image = i.clone();
+                    image.is_selected = false;
+                    image
+                })
+                .collect(),
+            erasers: contents
+                .erasers
+                .iter()
+                .filter(|e| self.erasers.contains(&e.id))
+                .copied()
+                .collect(),
+            rects: contents
+                .rects
+                .iter()
+                .filter(|r| self.shapes.contains(&r.id))
+                .copied()
+                .collect(),
+            ellipses: contents
+                .ellipses
+                .iter()
+                .filter(|e| self.shapes.contains(&e.id))
+                .copied()
+                .collect(),
+            arrows: contents
+                .arrows
+                .iter()
+                .filter(|a| self.shapes.contains(&a.id))
+                .copied()
+                .collect(),
+            texts: contents
+                .texts
+                .iter()
+                .filter(|t| self.shapes.contains(&t.id))
+                .cloned()
+                .collect(),
+            z: contents.z,
+        }
+    }
+
+    /// The box enclosing everything selected.
+    pub fn bounds(&self, contents: &Contents) -> Option<CanvasBox> {
+        let lines = contents
+            .lines
+            .iter()
+            .filter(|l| self.lines.contains(&l.id))
+            .map(|l| l as &dyn Bounds);
+        let images = contents
+            .images
+            .iter()
+            .filter(|i| self.images.contains(&i.id))
+            .map(|i| i as &dyn Bounds);
+        let erasers = contents
+            .erasers
+            .iter()
+            .filter(|e| self.erasers.contains(&e.id))
+            .map(|e| e as &dyn Bounds);
+        let rects = contents
+            .rects
+            .iter()
+            .filter(|r| self.shapes.contains(&r.id))
+            .map(|r| r as &dyn Bounds);
+        let ellipses = contents
+            .ellipses
+            .iter()
+            .filter(|e| self.shapes.contains(&e.id))
+            .map(|e| e as &dyn Bounds);
+        let arrows = contents
+            .arrows
+            .iter()
+            .filter(|a| self.shapes.contains(&a.id))
+            .map(|a| a as &dyn Bounds);
+        let texts = contents
+            .texts
+            .iter()
+            .filter(|t| self.shapes.contains(&t.id))
+            .map(|t| t as &dyn Bounds);
+        graphics::bounding_box(
+            lines
+                .chain(images)
+                .chain(erasers)
+                .chain(rects)
+                .chain(ellipses)
+                .chain(arrows)
+                .chain(texts),
+        )
+    }
+}
+
+/// A free-form closed shape drawn around what to select.
+#[derive(Debug, Clone)]
+pub struct Lasso {
+    pub points: Vec<CanvasPoint>,
+    pub color: Color,
+}
+
+impl Lasso {
+    pub fn new(color: Color) -> Self {
+        Self {
+            points: vec![],
+            color,
+        }
+    }
+
+    /// Even-odd test against the polygon closed by the first and last point.
+    pub fn contains(&self, p: CanvasPoint) -> bool {
+        if self.points.len() < 3 {
+            return false;
+        }
+
+        let mut inside = false;
+        let mut prev = *self.points.last().unwrap();
+        for &cur in &self.points {
+            if (cur.y > p.y) != (prev.y > p.y)
+                && p.x < (prev.x - cur.x) * (p.y - cur.y) / (prev.y - cur.y) + cur.x
+            {
+                inside = !inside;
+            }
+            prev = cur;
+        }
+
+        inside
+    }
+
+    pub fn encloses(&self, line: &Line) -> bool {
+        line.points.iter().all(|&p| self.contains(p))
+    }
+
+    pub fn encloses_box(&self, bounds: CanvasBox) -> bool {
+        [
+            bounds.min,
+            bounds.max,
+            CanvasPoint::new(bounds.min.x, bounds.max.y),
+            CanvasPoint::new(bounds.max.x, bounds.min.y),
+        ]
+        .into_iter()
+        .all(|p| self.contains(p))
+    }
+}
+
+impl InView for Lasso {
+    fn is_in_view(&self, _camera: &Camera) -> bool {
+        true
+    }
+}
+
+impl Drawable for Lasso {
+    fn z(&self) -> usize {
+        0
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let pts = self
+            .points
+            .iter()
+            .map(|p| {
+                let p = p.to_screen(camera);
+                Vector2::new(p.x, p.y)
+            })
+            .collect::<Vec<_>>();
+
+        for seg in pts.windows(2) {
+            d.draw_line_v(seg[0], seg[1], self.color);
+        }
+        // show where the shape is going to close
+        if let (Some(&first), Some(&last)) = (pts.first(), pts.last()) {
+            d.draw_line_v(last, first, self.color.alpha(0.4));
+        }
+    }
+}
+
+/// A frame drawn around the current selection.
+#[derive(Debug, Clone, Copy)]
+pub struct Outline {
+    pub bounds: CanvasBox,
+    pub color: Color,
+}
+
+impl InView for Outline {
+    fn is_in_view(&self, _camera: &Camera) -> bool {
+        true
+    }
+}
+
+impl Drawable for Outline {
+    fn z(&self) -> usize {
+        0
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, camera: &Camera) {
+        let rect = self.bounds.to_screen(camera).to_rect();
+
+        d.draw_rectangle_lines(
+            rect.min().x as i32,
+            rect.min().y as i32,
+            rect.size.width as i32,
+            rect.size.height as i32,
+            self.color,
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use widok::{CanvasBox, CanvasPoint, CanvasVector};
+
+/// A clean shape a freehand stroke was meant to be.
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Circle { center: CanvasPoint, radius: f32 },
+    Rect(CanvasBox),
+}
+
+impl Shape {
+    // how far off (relative to the shape's size) a stroke can be on average
+    const TOLERANCE: f32 = 0.08;
+
+    /// Fits a circle and a rectangle to a closed stroke and picks the closer one.
+    pub fn recognize(points: &[CanvasPoint]) -> Option<Self> {
+        if points.len() < 8 {
+            return None;
+        }
+
+        let bounds = CanvasBox::from_points(points);
+        let size = bounds.size();
+        let extent = size.width.min(size.height);
+        if extent <= 0.0 {
+            return None;
+        }
+        // the ends have to meet for the stroke to outline anything
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if (last - first).length() > 0.25 * size.width.max(size.height) {
+            return None;
+        }
+
+        let n = points.len() as f32;
+        let center = (points
+            .iter()
+            .fold(CanvasVector::zero(), |acc, p| acc + p.to_vector())
+            / n)
+            .to_point();
+        let dists = points.iter().map(|p| (*p - center).length());
+        let radius = dists.clone().sum::<f32>() / n;
+        let circle_err = dists.map(|d| (d - radius).abs()).sum::<f32>() / n / radius;
+
+        let rect_err = points
+            .iter()
+            .map(|p| {
+                (p.x - bounds.min.x)
+                    .min(bounds.max.x - p.x)
+                    .min(p.y - bounds.min.y)
+                    .min(bounds.max.y - p.y)
+            })
+            .sum::<f32>()
+            / n
+            / (extent / 2.0);
+
+        if circle_err.min(rect_err) > Self::TOLERANCE {
+            None
+        } else if circle_err < rect_err {
+            Some(Self::Circle { center, radius })
+        } else {
+            Some(Self::Rect(bounds))
+        }
+    }
+}
@@ -1,22 +1,39 @@
 use raylib::ffi;
 use raylib::{
     RaylibHandle, RaylibThread,
+    color::Color,
+    core::window::WindowState,
     ffi::{KeyboardKey, MouseButton, MouseCursor},
     math::Vector2,
     prelude::RaylibDraw,
     texture,
 };
-use std::ffi::c_void;
+use std::{ffi::c_void, iter, mem, time::Duration};
 use widok::{
-    CanvasLength, CanvasPoint, CanvasRect, CanvasSize, ScreenPoint, ScreenSize, ScreenSpace,
-    ScreenVector, ToCanvas, ToScreen,
+    Bounds, CanvasBox, CanvasLength, CanvasPoint, CanvasRect, CanvasSize, CanvasVector, Length,
+    ScreenPoint, ScreenSize, ScreenSpace, ScreenVector, ToCanvas, ToScreen,
 };
 
 use crate::clipboard::ImageData;
-use crate::graphics::{Brush, FilledCircle, StraightLine};
+use crate::config::{DrawingScroll, EraserMode};
+use crate::export;
+use crate::graphics::{
+    Brush, BrushMode, FilledCircle, Guide, PaletteStrip, Pulse, Rulers, ScaleBar, ScreenNote,
+    StatusBar, StraightLine, Vignette, draw_over_tracing, set_quality, ui_px,
+};
+use crate::input::{TextInput, edit_text};
+use crate::palette;
+use crate::selection::{Lasso, Outline, Selection};
+use crate::shape::Shape;
 use crate::{
-    command::{self, AddEraser, DrawLine},
-    graphics::{Drawable, Eraser, FilledRect, Image, ImageId, Line},
+    command::{
+        self, AddText, ClearAll, Command, CommandGroup, DrawArrow, DrawEllipse, DrawLine, DrawRect,
+        RemoveLines, RemoveSelection, RestoreCheckpoint, SplitLines,
+    },
+    graphics::{
+        Arrow, Contents, Drawable, Ellipse, Eraser, FilledRect, ImageId, Line, Menu, Rectangle,
+        ShapeId, Snapshot, Text,
+    },
     input::Action,
     scene::SceneData,
 };
@@ -38,11 +55,98 @@ fn mouse_pos(rl: &RaylibHandle) -> ScreenPoint {
     ScreenPoint::new(x, y)
 }
 
-fn mouse_delta(rl: &RaylibHandle) -> ScreenVector {
+// how long changing the color is highlighted for
+const COLOR_FEEDBACK: Duration = Duration::from_millis(400);
+
+fn mouse_delta(data: &SceneData, rl: &RaylibHandle) -> ScreenVector {
+    if data.config.absolute_input
+        && let Some(last) = data.last_mouse
+    {
+        return mouse_pos(rl) - last;
+    }
+
     let Vector2 { x, y } = rl.get_mouse_delta();
     ScreenVector::new(x, y)
 }
 
+/// Like `mouse_delta` but holds back movement until it adds up to more than
+/// the pan deadzone, so jitter cancels out and slow movement still counts.
+fn steady_mouse_delta(data: &mut SceneData, rl: &RaylibHandle) -> ScreenVector {
+    data.held_delta += mouse_delta(data, rl);
+    if data.held_delta.length() <= data.config.pan_deadzone {
+        ScreenVector::zero()
+    } else {
+        mem::replace(&mut data.held_delta, ScreenVector::zero())
+    }
+}
+
+/// How far the view center is past the contents, with enough margin for
+/// the contents to reach the window edges.
+fn pan_overshoot(data: &SceneData, rl: &RaylibHandle) -> CanvasVector {
+    let Some(bounds) = data.contents.bounding_box() else {
+        return CanvasVector::zero();
+    };
+    let screen = ScreenSize::new(rl.get_screen_width() as f32, rl.get_screen_height() as f32);
+    let zoom = data.camera.zoom().0;
+    let bounds = bounds.inflate(screen.width / 2.0 / zoom, screen.height / 2.0 / zoom);
+    let center = ScreenPoint::new(screen.width / 2.0, screen.height / 2.0).to_canvas(&data.camera);
+
+    let past = |v: f32, min: f32, max: f32| {
+        if v < min {
+            v - min
+        } else if v > max {
+            v - max
+        } else {
+            0.0
+        }
+    };
+    CanvasVector::new(
+        past(center.x, bounds.min.x, bounds.max.x),
+        past(center.y, bounds.min.y, bounds.max.y),
+    )
+}
+
+/// The size to update the camera with, `None` when it shouldn't be updated.
+fn camera_size(width: i32, height: i32, minimized: bool) -> Option<ScreenSize> {
+    let size = ScreenSize::new(width as f32, height as f32);
+    // a minimized window can report a zero size which would turn the
+    // camera position into NaNs
+    (!size.is_empty() && !minimized).then_some(size)
+}
+
+/// Lists the commands around the current undo position.
+fn draw_history(d: &mut impl RaylibDraw, data: &SceneData) {
+    const ENTRIES: usize = 10;
+    const MAX_LEN: usize = 60;
+
+    let invoker = &data.command_invoker;
+    let color = data.config.colors[0];
+    let undos = invoker.undos().rev().take(ENTRIES).collect::<Vec<_>>();
+    let skipped = invoker.undo_len() - undos.len();
+
+    let font = ui_px(20, data.ui_scale);
+    let x = ui_px(50, data.ui_scale);
+    let line = font + ui_px(5, data.ui_scale);
+    let mut y = ui_px(110, data.ui_scale);
+    if skipped > 0 {
+        d.draw_text(&format!("... {skipped} more"), x, y, font, color.alpha(0.4));
+        y += line;
+    }
+    for c in undos.into_iter().rev() {
+        d.draw_text(&command::describe(c, MAX_LEN), x, y, font, color);
+        y += line;
+    }
+
+    d.draw_text("> you are here", x, y, font, Color::LIME);
+    y += line;
+
+    // undone commands are faded
+    for c in invoker.redos().take(ENTRIES) {
+        d.draw_text(&command::describe(c, MAX_LEN), x, y, font, color.alpha(0.4));
+        y += line;
+    }
+}
+
 pub trait StateHandler {
     fn on_enter(&mut self, _data: &mut SceneData, _rl: &mut RaylibHandle) {}
     fn on_exit(&mut self, _data: &mut SceneData, _rl: &mut RaylibHandle) {}
@@ -55,53 +159,482 @@ pub trait StateHandler {
     ) -> Transition;
 
     fn draw(&self, data: &mut SceneData, thread: &RaylibThread, rl: &mut RaylibHandle) {
-        let size = ScreenSize::new(rl.get_screen_width() as f32, rl.get_screen_height() as f32);
-        data.camera.update(rl.get_frame_time(), mouse_pos(rl), size);
+        let (width, height) = (rl.get_screen_width(), rl.get_screen_height());
+        let size = ScreenSize::new(width as f32, height as f32);
+        let shown = camera_size(width, height, rl.is_window_minimized()).is_some();
+        if shown {
+            // a step long enough to finish any zoom or pan transition at once
+            let dt = if data.config.reduce_motion {
+                1.0
+            } else {
+                rl.get_frame_time()
+            };
+            data.camera.update(dt, mouse_pos(rl), size);
+            if data.resetting_view {
+                data.step_reset_view(dt);
+            }
+            data.step_momentum(dt);
+            if data.config.pan_resistance > 0.0 && data.pan_velocity != ScreenVector::zero() {
+                // a glide stops at the edge instead of leaving the contents behind
+                let over = pan_overshoot(data, rl) * data.camera.zoom().0;
+                if over != CanvasVector::zero() {
+                    data.camera.update_pos(ScreenVector::new(over.x, over.y));
+                    data.pan_velocity = ScreenVector::zero();
+                }
+            }
+        }
+        data.last_mouse = Some(mouse_pos(rl));
 
+        set_quality(data.config.quality);
         // DO NOT USE RaylibHandle::draw as it results in some input being dropped!
         let mut d = rl.begin_drawing(thread);
-        d.clear_background(data.config.background);
+        if let Some(area) = data.export.take() {
+            let path = data.config.export_file.clone();
+            match export::export_png(
+                &mut d,
+                thread,
+                &data.contents,
+                &data.selection,
+                &data.export_view(),
+                area,
+                &path,
+            ) {
+                Ok(()) => data.notify(format!("Exported {path}")),
+                Err(e) => {
+                    eprintln!("[ERROR] {e}");
+                    data.notify(e);
+                }
+            }
+        }
+        if let Some(selection) = data.copy_image.take() {
+            let copied = export::render_selection(
+                &mut d,
+                thread,
+                &data.contents,
+                &selection,
+                &data.export_view(),
+            )
+            .and_then(|image| match data.clipboard.as_mut() {
+                Some(clipboard) => clipboard.set_image(&image),
+                None => Err("No clipboard to copy to".to_string()),
+            });
+            match copied {
+                Ok(()) => data.notify("Copied the selection as an image".to_string()),
+                Err(e) => {
+                    eprintln!("[ERROR] {e}");
+                    data.notify(e);
+                }
+            }
+        }
+        if data.config.transparent {
+            d.clear_background(Color::BLANK);
+        } else {
+            d.clear_background(data.config.background);
+        }
 
-        let mut combined = Vec::<&dyn Drawable>::new();
-        combined.extend(data.contents.images.iter().map(|i| i as &dyn Drawable));
-        combined.extend(data.contents.lines.iter().map(|i| i as &dyn Drawable));
-        combined.extend(data.contents.erasers.iter().map(|i| i as &dyn Drawable));
-        combined = combined
-            .into_iter()
+        data.contents
+            .drawables(true)
             .filter(|i| i.is_in_view(&data.camera))
-            .collect::<Vec<_>>();
-        combined.sort_by_key(|i| i.z());
-        combined.extend(data.contents.overlay.iter().map(|i| &**i as &dyn Drawable));
-        combined.iter().for_each(|i| i.draw(&mut d, &data.camera));
+            .for_each(|i| {
+                draw_over_tracing(i, data.contents.visible_tracing(), &mut d, &data.camera)
+            });
+
+        // nothing to lay the HUD out in
+        if !shown {
+            return;
+        }
+
+        if data.config.show_rulers {
+            Rulers::new(
+                size,
+                data.config.background,
+                data.config.colors[0],
+                data.ui_scale,
+            )
+            .draw(&mut d, &data.camera);
+        }
+
+        if data.config.scale_bar {
+            ScaleBar {
+                size,
+                color: data.config.colors[0],
+                ui_scale: data.ui_scale,
+            }
+            .draw(&mut d, &data.camera);
+        }
+
+        if data.config.show_status {
+            StatusBar {
+                size,
+                brush: data.stroke_brush(),
+                color: data.config.colors[0],
+                ui_scale: data.ui_scale,
+            }
+            .draw(&mut d, &data.camera);
+        }
 
         if data.config.show_fps {
-            d.draw_fps(50, 50);
+            let font = ui_px(20, data.ui_scale);
+            let x = ui_px(50, data.ui_scale);
+            let fps = d.get_fps();
+            d.draw_text(&format!("{fps} FPS"), x, x, font, Color::LIME);
+
+            let stats = &data.stats;
+            d.draw_text(
+                &format!(
+                    "{:.2} ms, {}/{} visible, {} textures",
+                    stats.frame_time * 1000.0,
+                    stats.visible,
+                    stats.drawables,
+                    stats.textures
+                ),
+                x,
+                x + font + ui_px(5, data.ui_scale),
+                font,
+                Color::LIME,
+            );
+        }
+
+        if data.show_history {
+            draw_history(&mut d, data);
+        }
+
+        ScreenNote::draw_all(
+            &data.notes,
+            &mut d,
+            size,
+            data.config.background,
+            data.config.colors[0],
+            data.ui_scale,
+        );
+
+        if data.config.palette_strip
+            && let Some(t) = data.since_color_change()
+            && t < PaletteStrip::DURATION
+        {
+            PaletteStrip {
+                colors: data.palette(),
+                current: data.color_idx(),
+                progress: if data.config.reduce_motion {
+                    0.0
+                } else {
+                    t.as_secs_f32() / PaletteStrip::DURATION.as_secs_f32()
+                },
+                ui_scale: data.ui_scale,
+            }
+            .draw(&mut d, size, data.config.background);
+        }
+
+        if let Some(notification) = &data.notification
+            && !notification.expired()
+        {
+            notification.draw(
+                &mut d,
+                size,
+                data.config.background,
+                data.config.colors[0],
+                data.ui_scale,
+            );
+        }
+
+        if data.config.vignette {
+            Vignette {
+                size,
+                color: data.config.background,
+            }
+            .draw(&mut d, &data.camera);
         }
     }
 }
 
 pub struct Idle;
-struct Drawing;
-struct DrawingStraight;
-struct MovingCanvas;
-struct ModifyingImage(ImageId);
+struct Drawing {
+    // where the ink goes when the brush trails the cursor
+    lazy: Option<CanvasPoint>,
+}
+struct DrawingStraight {
+    // continue into a polyline instead of finishing the line
+    chain: bool,
+}
+struct DrawingPolyline;
+struct DrawingRect {
+    // the corner where the drag started
+    anchor: CanvasPoint,
+    id: ShapeId,
+}
+struct DrawingArrow {
+    start: CanvasPoint,
+    id: ShapeId,
+}
+struct MovingCanvas {
+    // recent speed of the mouse in pixels per second
+    velocity: ScreenVector,
+}
+struct ModifyingImage {
+    id: ImageId,
+    // where the left button went down, to tell a click from a drag
+    press: Option<ScreenPoint>,
+    // where the image was before it got nudged, all nudges are undone at once
+    nudged_from: Option<CanvasPoint>,
+}
 struct MovingImage {
     id: ImageId,
+    // where the drag started
+    grab: ScreenPoint,
     start_pos: CanvasPoint,
+    // where the image would be without snapping
+    raw_pos: CanvasPoint,
 }
 struct ResizingImage {
     id: ImageId,
     start_scale: CanvasLength,
+    // the far corner from the (fixed) top left one when resizing started
+    start_diagonal: CanvasVector,
+    grab: CanvasPoint,
+}
+struct EditingNote(usize);
+/// Asks for the path of an image file to open.
+struct OpeningImage {
+    path: String,
+}
+struct TypingText {
+    text: Text,
+}
+struct Lassoing {
+    lasso: Lasso,
+}
+struct Selecting {
+    // the corner where the drag started
+    anchor: CanvasPoint,
+}
+struct SelectedLines;
+struct ContextMenu {
+    id: ImageId,
+    menu: Menu,
+}
+pub struct ConfirmQuit {
+    menu: Menu,
+    // the exit key that opened it is still pressed on the first frame
+    entered: bool,
+}
+// asks before the text file replaces unsaved changes
+struct ConfirmLoad {
+    menu: Menu,
+}
+struct MovingSelection {
+    offset: CanvasVector,
+    // the offset without snapping
+    raw_offset: CanvasVector,
 }
 
 struct Erasing {
     eraser: Option<FilledRect>,
+    // erasing without ctrl held
+    button: Option<MouseButton>,
+}
+/// Removes whole lines touched by the cursor.
+struct ErasingStrokes {
+    button: Option<MouseButton>,
+    last: Option<CanvasPoint>,
+    removed: Vec<Line>,
+}
+/// Cuts away the parts of lines under the cursor.
+struct ErasingPixels {
+    button: Option<MouseButton>,
+    last: Option<CanvasPoint>,
+    // the original lines and the pieces left of them
+    removed: Vec<Line>,
+    added: Vec<Line>,
+}
+
+/// Replays the strokes in the order they were drawn, starting from the canvas
+/// as it was loaded.
+struct Playback {
+    // the actual contents, put back when playback ends
+    saved: Option<Contents>,
+    start: Snapshot,
+    strokes: Vec<Snapshot>,
+    // seconds between each stroke and the previous one
+    gaps: Vec<f32>,
+    next: usize,
+    clock: f32,
+    speed: f32,
 }
 
 impl Erasing {
-    pub fn new() -> Self {
-        Self { eraser: None }
+    pub fn new(button: Option<MouseButton>) -> Self {
+        Self {
+            eraser: None,
+            button,
+        }
+    }
+}
+
+/// The state for the configured eraser, `button` is the one erasing without ctrl.
+fn eraser(data: &SceneData, button: Option<MouseButton>) -> Box<dyn StateHandler> {
+    match data.config.eraser {
+        EraserMode::Area => Box::new(Erasing::new(button)),
+        EraserMode::Stroke => Box::new(ErasingStrokes {
+            button,
+            last: None,
+            removed: vec![],
+        }),
+        EraserMode::Pixel => Box::new(ErasingPixels {
+            button,
+            last: None,
+            removed: vec![],
+            added: vec![],
+        }),
+    }
+}
+
+/// Shows how big the stroke and pixel erasers are.
+fn push_eraser_cursor(data: &mut SceneData, mouse: ScreenPoint, radius: f32) {
+    data.contents.overlay.push(Box::new(FilledCircle {
+        pos: mouse,
+        brush: Brush {
+            color: data.config.colors[0].alpha(0.3),
+            thickness: CanvasLength::new(2.0 * radius),
+            mode: BrushMode::Pen,
+        },
+    }));
+}
+
+fn is_erasing(data: &SceneData, button: Option<MouseButton>, rl: &RaylibHandle) -> bool {
+    match button {
+        Some(button) => rl.is_mouse_button_down(button),
+        None => data.config.erase.is_down(rl),
+    }
+}
+
+/// Puts a copy of the image slightly offset on top of everything, returns its id.
+fn duplicate_image(data: &mut SceneData, id: ImageId) -> Option<ImageId> {
+    const OFFSET: f32 = 20.0;
+
+    let img = data.contents.image(id)?;
+    let mut copy = img.clone();
+    copy.is_selected = false;
+    copy.pos += ScreenVector::new(OFFSET, OFFSET).to_canvas(&data.camera);
+    copy.id = data.contents.next_image_id();
+    data.contents.z += 1;
+    copy.z = data.contents.z;
+
+    let id = copy.id;
+    data.contents.images.push(copy.clone());
+    data.command_invoker.push(command::PasteImage::new(copy));
+    Some(id)
+}
+
+fn load_text(data: &mut SceneData, rl: &mut RaylibHandle, thread: &RaylibThread) {
+    match data.load_text(rl, thread) {
+        Ok(()) => data.notify(format!("Loaded {}", data.config.text_file)),
+        Err(e) => {
+            eprintln!("[ERROR] {e}");
+            data.notify(e);
+        }
+    }
+}
+
+/// Applies `change` to the image's layer and records it if the layer moved.
+fn change_layer(data: &mut SceneData, id: ImageId, change: fn(&mut Contents, ImageId)) {
+    let Some(start_z) = data.contents.image(id).map(|i| i.z) else {
+        return;
+    };
+    change(&mut data.contents, id);
+    let end_z = data.contents.image(id).unwrap().z;
+    if end_z != start_z {
+        data.command_invoker
+            .push(command::ChangeLayer::new(id, start_z, end_z));
+    }
+}
+
+/// Inserts the drawables copied within kajet centered at `mouse` and selects them.
+fn paste_copied(data: &mut SceneData, mouse: ScreenPoint) -> bool {
+    let copied = data.copied.clone();
+    let Some(bounds) = copied.bounding_box() else {
+        return false;
+    };
+    let offset = mouse.to_canvas(&data.camera) - bounds.center();
+    // the copies keep their stacking order, above everything else
+    let layers = copied
+        .lines
+        .iter()
+        .map(|l| l.z())
+        .chain(copied.images.iter().map(|i| i.z))
+        .chain(copied.erasers.iter().map(|e| e.z()))
+        .chain(copied.rects.iter().map(|r| r.z()))
+        .chain(copied.ellipses.iter().map(|e| e.z()))
+        .chain(copied.arrows.iter().map(|a| a.z()))
+        .chain(copied.texts.iter().map(|t| t.z()));
+    let (lowest, highest) = layers.fold((usize::MAX, 0), |(lo, hi), z| (lo.min(z), hi.max(z)));
+    let base = data.contents.z;
+    let layer = |z: usize| base + z - lowest;
+
+    let mut commands = Vec::<Box<dyn Command>>::new();
+    data.selection.mark(&mut data.contents, false);
+    data.selection.clear();
+    for line in &copied.lines {
+        let id = data.contents.next_line_id();
+        let mut copy = line.copy_as(id, layer(line.z()));
+        copy.translate(offset);
+        data.contents.lines.push(copy.clone());
+        data.selection.lines.push(id);
+        commands.push(Box::new(DrawLine::new(copy)));
+    }
+    for image in &copied.images {
+        let mut copy = image.clone();
+        copy.id = data.contents.next_image_id();
+        copy.z = layer(image.z);
+        copy.pos += offset;
+        data.contents.images.push(copy.clone());
+        data.selection.images.push(copy.id);
+        commands.push(Box::new(command::PasteImage::new(copy)));
+    }
+    for eraser in &copied.erasers {
+        let id = data.contents.next_eraser_id();
+        let mut copy = eraser.copy_as(id, layer(eraser.z()));
+        copy.translate(offset);
+        data.contents.erasers.push(copy);
+        data.selection.erasers.push(id);
+        commands.push(Box::new(command::AddEraser::new(copy)));
+    }
+    for rect in &copied.rects {
+        let id = data.contents.next_shape_id();
+        let mut copy = rect.copy_as(id, layer(rect.z()));
+        copy.translate(offset);
+        data.contents.rects.push(copy);
+        data.selection.shapes.push(id);
+        commands.push(Box::new(DrawRect::new(copy)));
     }
+    for ellipse in &copied.ellipses {
+        let id = data.contents.next_shape_id();
+        let mut copy = ellipse.copy_as(id, layer(ellipse.z()));
+        copy.translate(offset);
+        data.contents.ellipses.push(copy);
+        data.selection.shapes.push(id);
+        commands.push(Box::new(DrawEllipse::new(copy)));
+    }
+    for arrow in &copied.arrows {
+        let id = data.contents.next_shape_id();
+        let mut copy = arrow.copy_as(id, layer(arrow.z()));
+        copy.translate(offset);
+        data.contents.arrows.push(copy);
+        data.selection.shapes.push(id);
+        commands.push(Box::new(DrawArrow::new(copy)));
+    }
+    for text in &copied.texts {
+        let id = data.contents.next_shape_id();
+        let mut copy = text.copy_as(id, layer(text.z()));
+        copy.translate(offset);
+        data.contents.texts.push(copy.clone());
+        data.selection.shapes.push(id);
+        commands.push(Box::new(AddText::new(copy)));
+    }
+    data.contents.z = layer(highest) + 1;
+    data.selection.mark(&mut data.contents, true);
+    data.command_invoker.push(CommandGroup::new(commands));
+
+    true
 }
 
 pub enum Transition {
@@ -129,6 +662,18 @@ impl Idle {
         unsafe { texture::Image::from_raw(rl_image) }
     }
 
+    /// Loads a clipboard image into a texture, returns it along with
+    /// how much it was downscaled.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_clipboard_texture(
+        data: &SceneData,
+        thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+        image_data: &ImageData,
+    ) -> Option<(texture::Texture2D, f32)> {
+        data.load_scaled_texture(Self::image_from_arboard(image_data), rl, thread)
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     fn try_paste_image(
         &self,
@@ -137,39 +682,20 @@ impl Idle {
         rl: &mut RaylibHandle,
         image_data: ImageData,
     ) {
-        let rl_image = Self::image_from_arboard(&image_data);
-
-        let Ok(texture) = rl.load_texture_from_image(thread, &rl_image) else {
-            return;
-        };
-
-        let mouse = mouse_pos(rl);
-        let delta = ScreenVector::new(
-            image_data.width as f32 / 2.0,
-            image_data.height as f32 / 2.0,
-        );
-        let pos = mouse - delta;
-
-        // TODO: consider adding this to contents instead of exposing this api
-        data.contents.z += 1;
-        let image = Image::new(
-            pos.to_canvas(&data.camera),
-            texture,
-            CanvasLength::new(1.0 / data.camera.zoom().0),
-            data.contents.next_image_id(),
-            data.contents.z,
-            &data.config,
-        );
-        data.contents.images.push(image.clone());
-        data.command_invoker.push(command::PasteImage::new(image));
+        if let Some((texture, scale)) = Self::load_clipboard_texture(data, thread, rl, &image_data)
+        {
+            data.add_image(texture, scale, mouse_pos(rl), thread);
+        }
     }
 }
 
 impl StateHandler for Idle {
-    fn on_enter(&mut self, _data: &mut SceneData, rl: &mut RaylibHandle) {
+    fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
         #[cfg(not(target_arch = "wasm32"))]
         rl.hide_cursor();
         rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_DEFAULT);
+        data.selection.mark(&mut data.contents, false);
+        data.selection.clear();
     }
 
     fn step(
@@ -182,50 +708,283 @@ impl StateHandler for Idle {
 
         data.contents.overlay.push(Box::new(FilledCircle {
             pos: mouse,
-            brush: Brush {
-                color: data.brush.color,
-                thickness: data.brush.thickness.to_screen(&data.camera),
-            },
+            brush: data.stroke_brush(),
         }));
 
+        if rl.is_file_dropped() {
+            // how far apart (in pixels) images dropped together land
+            const FAN_OUT: f32 = 30.0;
+
+            let paths = rl
+                .load_dropped_files()
+                .paths()
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>();
+            for (i, path) in paths.iter().enumerate() {
+                let offset = FAN_OUT * i as f32;
+                let center = mouse + ScreenVector::new(offset, offset);
+                if let Err(e) = data.open_image(path, center, rl, thread) {
+                    eprintln!("[ERROR] {e}");
+                    data.notify(e);
+                }
+            }
+        }
+
+        if !data.config.reduce_motion
+            && let Some(t) = data.since_color_change()
+            && t < COLOR_FEEDBACK
+        {
+            data.contents.overlay.push(Box::new(Pulse {
+                pos: mouse,
+                brush: data.stroke_brush(),
+                progress: t.as_secs_f32() / COLOR_FEEDBACK.as_secs_f32(),
+            }));
+        }
+
         if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
-            if rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+            if data.config.select_image.is_down(rl)
                 && let Some(id) = data.image_under_cursor(mouse)
             {
-                return Transition::Switch(Box::new(ModifyingImage(id)));
+                return Transition::Switch(Box::new(ModifyingImage::new(id)));
+            }
+            if data.config.select.is_down(rl) {
+                return Transition::Switch(Box::new(Selecting {
+                    anchor: mouse.to_canvas(&data.camera),
+                }));
+            }
+            if data.config.draw_arrow.is_down(rl) {
+                return Transition::Switch(Box::new(DrawingArrow {
+                    start: mouse.to_canvas(&data.camera),
+                    id: data.contents.next_shape_id(),
+                }));
+            }
+            if data.config.draw_rect.is_down(rl) {
+                return Transition::Switch(Box::new(DrawingRect {
+                    anchor: mouse.to_canvas(&data.camera),
+                    id: data.contents.next_shape_id(),
+                }));
             }
-            return Transition::Switch(Box::new(Drawing));
+            return Transition::Switch(Box::new(Drawing::new()));
         }
 
-        if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
-            if rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) {
-                return Transition::Switch(Box::new(Erasing::new()));
-            }
+        if let Some(button) = data.config.erase_button
+            && rl.is_mouse_button_down(button)
+        {
+            return Transition::Switch(eraser(data, Some(button)));
+        }
+
+        if data.config.context_menu
+            && rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT)
+            && !data.config.erase.is_down(rl)
+            && let Some(id) = data.image_under_cursor(mouse)
+        {
+            return Transition::Switch(Box::new(ContextMenu::new(data, rl, id, mouse)));
+        }
+
+        if data.config.erase.is_down(rl) {
+            return Transition::Switch(eraser(data, None));
+        }
 
-            return Transition::Switch(Box::new(MovingCanvas));
+        if data.config.move_canvas.is_down(rl) {
+            return Transition::Switch(Box::new(MovingCanvas {
+                velocity: ScreenVector::zero(),
+            }));
         }
 
         let scroll = rl.get_mouse_wheel_move_v();
         if scroll.y != 0.0 {
-            if rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) {
+            if data.config.scroll_thickness.is_down(rl) {
                 data.update_thickness(scroll.y);
             } else {
-                data.update_zoom(scroll.y);
+                data.update_zoom(rl, scroll.y);
             }
         }
 
         match data.input_handler.interpret(rl) {
-            Action::Undo => data.command_invoker.undo(&mut data.contents),
-            Action::Redo => data.command_invoker.redo(&mut data.contents),
+            Action::Undo => {
+                if data.command_invoker.undo_len() > 0 {
+                    data.command_invoker.undo(&mut data.contents);
+                } else if data.config.notify_empty_undo {
+                    data.notify("Nothing to undo".to_string());
+                }
+            }
+            Action::Redo => {
+                if data.command_invoker.redo_len() > 0 {
+                    data.command_invoker.redo(&mut data.contents);
+                } else if data.config.notify_empty_undo {
+                    data.notify("Nothing to redo".to_string());
+                }
+            }
             Action::NextColor => data.update_color(true),
             Action::PrevColor => data.update_color(false),
-            Action::Paste =>
-            {
+            Action::SelectColor(n) => data.select_color(n.into()),
+            Action::Paste => {
                 #[cfg(not(target_arch = "wasm32"))]
-                if let Some(ref mut clipboard) = data.clipboard {
-                    if let Ok(image_data) = clipboard.get_image() {
-                        self.try_paste_image(data, thread, rl, image_data);
-                    }
+                if let Some(ref mut clipboard) = data.clipboard
+                    && let Ok(image_data) = clipboard.get_image()
+                    && data.copied_over != Some(image_data.fingerprint())
+                {
+                    self.try_paste_image(data, thread, rl, image_data);
+                    return Transition::Stay;
+                }
+                if paste_copied(data, mouse) {
+                    return Transition::Switch(Box::new(SelectedLines));
+                }
+                if data.config.notify_empty_paste {
+                    data.notify("Nothing to paste".to_string());
+                }
+            }
+            Action::OpenImage => {
+                return Transition::Switch(Box::new(OpeningImage {
+                    path: String::new(),
+                }));
+            }
+            Action::AddText => {
+                let text = Text::new(
+                    mouse.to_canvas(&data.camera),
+                    String::new(),
+                    CanvasLength::new(data.config.text_size / data.camera.zoom().0),
+                    data.stroke_brush().color,
+                    data.contents.next_shape_id(),
+                    data.contents.z,
+                );
+                return Transition::Switch(Box::new(TypingText { text }));
+            }
+            Action::AddNote => {
+                data.notes.push(ScreenNote::default());
+                return Transition::Switch(Box::new(EditingNote(data.notes.len() - 1)));
+            }
+            Action::RemoveNote => {
+                data.notes.pop();
+            }
+            Action::Lasso => {
+                return Transition::Switch(Box::new(Lassoing::new(data.config.colors[0])));
+            }
+            Action::Stats => {
+                let summary = data.canvas_summary(rl.get_frame_time());
+                eprintln!("[INFO] {summary}");
+                data.notify(summary);
+            }
+            Action::History => data.show_history = !data.show_history,
+            Action::Checkpoint => {
+                data.checkpoints.push(data.contents.snapshot());
+                data.checkpoint_idx = None;
+                data.notify(format!("Checkpoint {}", data.checkpoints.len()));
+            }
+            Action::RestoreCheckpoint => {
+                let count = data.checkpoints.len();
+                if count == 0 {
+                    data.notify("No checkpoints".to_string());
+                } else {
+                    let idx = match data.checkpoint_idx {
+                        Some(0) | None => count - 1,
+                        Some(i) => i - 1,
+                    };
+                    data.checkpoint_idx = Some(idx);
+                    data.selection.clear();
+                    let mut restore = RestoreCheckpoint::new(data.checkpoints[idx].clone());
+                    restore.execute(&mut data.contents);
+                    data.command_invoker.push(restore);
+                    data.notify(format!("Restored checkpoint {}", idx + 1));
+                }
+            }
+            Action::Clear => {
+                if data.contents.bounding_box().is_some() {
+                    data.selection.clear();
+                    let mut clear = ClearAll::default();
+                    clear.execute(&mut data.contents);
+                    data.command_invoker.push(clear);
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Action::ToggleTopmost => {
+                let topmost = WindowState::default().set_window_topmost(true);
+                // RaylibHandle::get_window_state drops every flag it reads
+                let is_topmost =
+                    unsafe { ffi::IsWindowState(ffi::ConfigFlags::FLAG_WINDOW_TOPMOST as u32) };
+                if is_topmost {
+                    rl.clear_window_state(topmost);
+                    data.notify("Always on top off".to_string());
+                } else {
+                    rl.set_window_state(topmost);
+                    data.notify("Always on top on".to_string());
+                }
+            }
+            Action::ResetView => data.reset_view(),
+            Action::ReloadConfig => match data.reload_config(rl) {
+                Ok(()) => data.notify("Reloaded the config".to_string()),
+                Err(e) => {
+                    eprintln!("[ERROR] Couldn't reload the config: {e}");
+                    data.notify(e);
+                }
+            },
+            Action::Save => match data.save() {
+                Ok(path) => data.notify(format!("Saved to {}", path.display())),
+                Err(e) => {
+                    eprintln!("[ERROR] {e}");
+                    data.notify(e);
+                }
+            },
+            Action::Export(area) => data.export = Some(area),
+            Action::ExportSvg => match data.export_svg(None) {
+                Ok(path) => data.notify(format!("Exported {}", path.display())),
+                Err(e) => {
+                    eprintln!("[ERROR] {e}");
+                    data.notify(e);
+                }
+            },
+            Action::SaveText => match data.save_text() {
+                Ok(()) => data.notify(format!("Saved to {}", data.config.text_file)),
+                Err(e) => {
+                    eprintln!("[ERROR] {e}");
+                    data.notify(e);
+                }
+            },
+            Action::LoadText => {
+                if data.command_invoker.is_dirty() {
+                    return Transition::Switch(Box::new(ConfirmLoad::new(data, rl)));
+                }
+                load_text(data, rl, thread);
+            }
+            Action::Trace => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(ref mut clipboard) = data.clipboard
+                    && let Ok(image_data) = clipboard.get_image()
+                    && let Some((texture, scale)) =
+                        Self::load_clipboard_texture(data, thread, rl, &image_data)
+                {
+                    let scale = scale / data.camera.zoom().0;
+                    let size = CanvasVector::new(
+                        image_data.width as f32 * scale,
+                        image_data.height as f32 * scale,
+                    );
+                    let pos = mouse.to_canvas(&data.camera) - size / 2.0;
+                    data.set_tracing(texture, pos, scale, thread);
+                    return Transition::Stay;
+                }
+                data.notify("No image to trace in the clipboard".to_string());
+            }
+            Action::ToggleHighlighter => {
+                data.brush.mode = match data.brush.mode {
+                    BrushMode::Pen => BrushMode::Highlighter,
+                    BrushMode::Highlighter => BrushMode::Pen,
+                };
+                let state = if data.brush.mode == BrushMode::Highlighter {
+                    "on"
+                } else {
+                    "off"
+                };
+                data.notify(format!("Highlighter {state}"));
+            }
+            Action::ToggleTracing => {
+                data.contents.show_tracing = !data.contents.show_tracing;
+            }
+            Action::Playback => {
+                if data.command_invoker.undo_len() == 0 {
+                    data.notify("Nothing to play back".to_string());
+                } else {
+                    return Transition::Switch(Box::new(Playback::new(data)));
                 }
             }
             _ => {}
@@ -235,6 +994,28 @@ impl StateHandler for Idle {
     }
 }
 
+impl Drawing {
+    pub fn new() -> Self {
+        Self { lazy: None }
+    }
+
+    /// Drags the brush behind `pos` once it's further away than the lazy radius.
+    fn follow(&mut self, data: &SceneData, pos: CanvasPoint) -> CanvasPoint {
+        let rope = data.config.lazy_radius / data.camera.zoom().0;
+        if rope == 0.0 {
+            return pos;
+        }
+
+        let brush = self.lazy.get_or_insert(pos);
+        let d = pos - *brush;
+        let len = d.length();
+        if len > rope {
+            *brush += d * ((len - rope) / len);
+        }
+        *brush
+    }
+}
+
 impl StateHandler for Drawing {
     fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
         #[cfg(not(target_arch = "wasm32"))]
@@ -242,17 +1023,58 @@ impl StateHandler for Drawing {
         rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_DEFAULT);
 
         let pos = mouse_pos(rl).to_canvas(&data.camera);
+        self.lazy = Some(pos);
+        let id = data.contents.next_line_id();
         data.contents
             .lines
-            .push(Line::new(pos, data.brush, data.contents.z));
+            .push(Line::new(pos, data.stroke_brush(), id, data.contents.z));
     }
 
-    fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
-        if let Some(last) = data.contents.lines.last_mut() {
+    fn on_exit(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        // turns smaller than this are hardly noticeable
+        const MAX_COLLINEAR_ANGLE: f32 = 2.0 * std::f32::consts::PI / 180.0;
+
+        let max_deviation = data.config.straighten / data.camera.zoom().0;
+        let recognize = data.config.recognize_shapes && data.snapping(rl);
+        let shape = data
+            .contents
+            .lines
+            .last()
+            .filter(|_| recognize)
+            .and_then(|l| Shape::recognize(&l.points));
+        if let Some(shape) = shape {
+            // the stroke gets replaced by the shape it outlined
+            let line = data.contents.lines.pop().unwrap();
+            let id = data.contents.next_shape_id();
+            let z = data.contents.z;
+            match shape {
+                Shape::Rect(b) => {
+                    let rect = Rectangle::new(b.min, b.max, line.brush, id, z);
+                    data.contents.rects.push(rect);
+                    data.command_invoker.push(DrawRect::new(rect));
+                }
+                Shape::Circle { center, radius } => {
+                    let r = CanvasVector::splat(radius);
+                    let bounds = CanvasBox::new(center - r, center + r);
+                    let ellipse = Ellipse::new(bounds, line.brush, id, z);
+                    data.contents.ellipses.push(ellipse);
+                    data.command_invoker.push(DrawEllipse::new(ellipse));
+                }
+            }
+            data.contents.z += 1;
+        } else if let Some(last) = data.contents.lines.last_mut() {
+            if max_deviation == 0.0 || !last.straighten(max_deviation) {
+                last.drop_collinear(MAX_COLLINEAR_ANGLE);
+            }
             last.finished = true;
             let cmd = DrawLine::new(data.contents.lines.last().unwrap().clone());
             data.command_invoker.push(cmd);
         }
+
+        // switching to a straight line doesn't end the stroke
+        if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+            data.stroke_finished();
+        }
     }
 
     fn step(
@@ -266,28 +1088,48 @@ impl StateHandler for Drawing {
         }
 
         let mouse = mouse_pos(rl);
+        let pos = self.follow(data, mouse.to_canvas(&data.camera));
+        let brush_pos = pos.to_screen(&data.camera);
+        if data.config.lazy_radius > 0.0 {
+            data.contents.overlay.push(Box::new(StraightLine {
+                start: brush_pos,
+                end: mouse,
+                brush: Brush {
+                    color: data.config.colors[0],
+                    thickness: Length::new(1.0),
+                    mode: BrushMode::Pen,
+                },
+            }));
+        }
         data.contents.overlay.push(Box::new(FilledCircle {
-            pos: mouse,
-            brush: Brush {
-                color: data.brush.color,
-                thickness: data.brush.thickness.to_screen(&data.camera),
-            },
+            pos: brush_pos,
+            brush: data.stroke_brush(),
         }));
 
         if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
-            return Transition::Switch(Box::new(DrawingStraight));
+            return Transition::Switch(Box::new(DrawingStraight::new()));
+        }
+
+        // points are in canvas space so zooming doesn't affect what's drawn so far
+        let scroll = rl.get_mouse_wheel_move_v();
+        if scroll.y != 0.0 {
+            match data.config.drawing_scroll {
+                DrawingScroll::Nothing => {}
+                DrawingScroll::Zoom => data.update_zoom(rl, scroll.y),
+                DrawingScroll::Thickness => data.update_thickness(scroll.y),
+            }
         }
 
-        let pos = mouse.to_canvas(&data.camera);
         let line = data
             .contents
             .lines
             .last_mut()
             .expect("A line should be present because we insert a new one on_enter");
         if line.finished {
+            let id = data.contents.next_line_id();
             data.contents
                 .lines
-                .push(Line::new(pos, data.brush, data.contents.z));
+                .push(Line::new(pos, data.stroke_brush(), id, data.contents.z));
         } else if CanvasLength::new(line.points.last().unwrap().distance_to(pos))
             .to_screen(&data.camera)
             .0
@@ -300,27 +1142,61 @@ impl StateHandler for Drawing {
     }
 }
 
+impl DrawingStraight {
+    pub fn new() -> Self {
+        Self { chain: false }
+    }
+}
+
+impl DrawingStraight {
+    /// Where the line from `start` ends, on a multiple of the snap angle
+    /// while `angle_snap` is held.
+    fn end(data: &SceneData, rl: &RaylibHandle, start: CanvasPoint) -> CanvasPoint {
+        let pos = mouse_pos(rl).to_canvas(&data.camera);
+        if !data.config.angle_snap.is_down(rl) {
+            return pos;
+        }
+
+        let v = pos - start;
+        let step = data.config.snap_angle.to_radians();
+        let angle = (v.y.atan2(v.x) / step).round() * step;
+        let dir = CanvasVector::new(angle.cos(), angle.sin());
+        start + dir * v.dot(dir)
+    }
+}
+
 impl StateHandler for DrawingStraight {
     fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
         let pos = mouse_pos(rl).to_canvas(&data.camera);
 
-        data.contents
-            .lines
-            .push(Line::new(pos, data.brush, data.contents.z));
+        let id = data.contents.next_line_id();
+        let mut line = Line::new(pos, data.stroke_brush(), id, data.contents.z);
+        line.segmented = true;
+        data.contents.lines.push(line);
     }
 
     fn on_exit(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
-        let pos = mouse_pos(rl).to_canvas(&data.camera);
+        let start = *data
+            .contents
+            .lines
+            .last()
+            .and_then(|l| l.points.last())
+            .expect("There should be a line already when exiting the straight line drawing state.");
+        let pos = Self::end(data, rl, start);
 
-        let line =
-            data.contents.lines.last_mut().expect(
-                "There should be a line already when exiting the straight line drawing state.",
-            );
+        let line = data.contents.lines.last_mut().unwrap();
         line.points.push(pos);
+        if self.chain {
+            return;
+        }
         line.finished = true;
 
         let cmd = DrawLine::new(data.contents.lines.last().unwrap().clone());
         data.command_invoker.push(cmd);
+
+        if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+            data.stroke_finished();
+        }
     }
 
     fn step(
@@ -331,21 +1207,27 @@ impl StateHandler for DrawingStraight {
     ) -> Transition {
         if !rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
             if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
-                return Transition::Switch(Box::new(Drawing));
+                return Transition::Switch(Box::new(Drawing::new()));
             }
             return Transition::Switch(Box::new(Idle));
         }
         if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+            if data.config.polyline {
+                self.chain = true;
+                return Transition::Switch(Box::new(DrawingPolyline));
+            }
             return Transition::Switch(Box::new(Idle));
         }
 
         if let Some(line) = data.contents.lines.last() {
+            let start = *line.points.last().unwrap();
             data.contents.overlay.push(Box::new(StraightLine {
-                start: line.points.last().unwrap().to_screen(&data.camera),
-                end: mouse_pos(rl),
+                start: start.to_screen(&data.camera),
+                end: Self::end(data, rl, start).to_screen(&data.camera),
                 brush: Brush::<ScreenSpace> {
                     color: data.brush.color,
-                    thickness: data.brush.thickness.to_screen(&data.camera),
+                    thickness: data.stroke_brush().thickness.to_screen(&data.camera),
+                    mode: data.brush.mode,
                 },
             }));
         }
@@ -354,10 +1236,29 @@ impl StateHandler for DrawingStraight {
     }
 }
 
-impl StateHandler for MovingCanvas {
-    fn on_enter(&mut self, _data: &mut SceneData, rl: &mut RaylibHandle) {
-        rl.show_cursor();
-        rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_POINTING_HAND);
+impl DrawingRect {
+    fn rect(&self, data: &SceneData, rl: &RaylibHandle) -> Rectangle {
+        let corner = mouse_pos(rl).to_canvas(&data.camera);
+        Rectangle::new(
+            self.anchor,
+            corner,
+            data.stroke_brush(),
+            self.id,
+            data.contents.z,
+        )
+    }
+}
+
+impl StateHandler for DrawingRect {
+    fn on_exit(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        let rect = self.rect(data, rl);
+        if rect.rect.is_empty() {
+            return;
+        }
+        data.contents.rects.push(rect);
+        data.contents.z += 1;
+        data.command_invoker.push(DrawRect::new(rect));
+        data.stroke_finished();
     }
 
     fn step(
@@ -366,18 +1267,183 @@ impl StateHandler for MovingCanvas {
         _thread: &RaylibThread,
         rl: &mut RaylibHandle,
     ) -> Transition {
-        if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
+        if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        let rect = self.rect(data, rl);
+        data.contents.overlay.push(Box::new(rect));
+        Transition::Stay
+    }
+}
+
+impl DrawingArrow {
+    fn arrow(&self, data: &SceneData, rl: &RaylibHandle) -> Arrow {
+        let end = mouse_pos(rl).to_canvas(&data.camera);
+        Arrow::new(
+            self.start,
+            end,
+            data.stroke_brush(),
+            self.id,
+            data.contents.z,
+        )
+    }
+}
+
+impl StateHandler for DrawingArrow {
+    fn on_exit(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        let arrow = self.arrow(data, rl);
+        if arrow.start == arrow.end {
+            return;
+        }
+        data.contents.arrows.push(arrow);
+        data.contents.z += 1;
+        data.command_invoker.push(DrawArrow::new(arrow));
+        data.stroke_finished();
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        let arrow = self.arrow(data, rl);
+        data.contents.overlay.push(Box::new(arrow));
+        Transition::Stay
+    }
+}
+
+impl StateHandler for DrawingPolyline {
+    fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        let line = data
+            .contents
+            .lines
+            .last_mut()
+            .expect("There should be a line already when exiting the polyline drawing state.");
+        line.finished = true;
+
+        let cmd = DrawLine::new(data.contents.lines.last().unwrap().clone());
+        data.command_invoker.push(cmd);
+        data.stroke_finished();
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE)
+            || rl.is_key_pressed(KeyboardKey::KEY_ENTER)
+            || rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT)
+        {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        let scroll = rl.get_mouse_wheel_move_v();
+        if scroll.y != 0.0 {
+            data.update_zoom(rl, scroll.y);
+        }
+
+        let mouse = mouse_pos(rl);
+        let line = data
+            .contents
+            .lines
+            .last_mut()
+            .expect("A line should be present because DrawingStraight leaves it unfinished");
+        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            line.points.push(mouse.to_canvas(&data.camera));
+        }
+
+        let start = line.points.last().unwrap().to_screen(&data.camera);
+        let brush = Brush::<ScreenSpace> {
+            color: data.brush.color,
+            thickness: data.stroke_brush().thickness.to_screen(&data.camera),
+            mode: data.brush.mode,
+        };
+        data.contents.overlay.push(Box::new(StraightLine {
+            start,
+            end: mouse,
+            brush,
+        }));
+        data.contents.overlay.push(Box::new(FilledCircle {
+            pos: mouse,
+            brush: data.stroke_brush(),
+        }));
+
+        Transition::Stay
+    }
+}
+
+impl StateHandler for MovingCanvas {
+    fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        data.resetting_view = false;
+        data.pan_velocity = ScreenVector::zero();
+        rl.show_cursor();
+        rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_POINTING_HAND);
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if !data.config.move_canvas.is_down(rl) {
             return Transition::Switch(Box::new(Idle));
         }
 
-        data.camera.update_pos(mouse_delta(rl));
+        let mut delta = steady_mouse_delta(data, rl);
+        let strength = data.config.pan_resistance;
+        if strength > 0.0 {
+            // overshoot (in pixels) at which moving further away takes twice the effort
+            const DISTANCE: f32 = 200.0;
+
+            let over = pan_overshoot(data, rl) * data.camera.zoom().0;
+            // the view moves against the mouse
+            let resist = |d: f32, o: f32| {
+                if d * o < 0.0 {
+                    d / (1.0 + strength * o.abs() / DISTANCE)
+                } else {
+                    d
+                }
+            };
+            delta = ScreenVector::new(resist(delta.x, over.x), resist(delta.y, over.y));
+        }
+        data.camera.update_pos(delta);
+
+        let dt = rl.get_frame_time();
+        if dt > 0.0 {
+            // smoothed so a single jerky frame doesn't fling the camera
+            self.velocity = self.velocity.lerp(delta / dt, 0.5);
+        }
 
         Transition::Stay
     }
+
+    fn on_exit(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        if data.config.pan_resistance > 0.0 {
+            let over = pan_overshoot(data, rl) * data.camera.zoom().0;
+            if over != CanvasVector::zero() {
+                // springing back, momentum would carry it away again
+                data.camera.update_pos(ScreenVector::new(over.x, over.y));
+                return;
+            }
+        }
+        if data.config.pan_momentum && !data.config.reduce_motion {
+            data.pan_velocity = self.velocity;
+        }
+    }
 }
 
 impl StateHandler for Erasing {
     fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        data.command_invoker.end_group();
         rl.show_cursor();
         rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_ARROW);
 
@@ -390,12 +1456,15 @@ impl StateHandler for Erasing {
 
     fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
         if let Some(eraser) = &self.eraser {
+            let id = data.contents.next_eraser_id();
             data.contents
                 .erasers
-                .push(Eraser::new(eraser.rect, eraser.color, data.contents.z));
+                .push(Eraser::new(eraser.rect, eraser.color, id, data.contents.z));
             data.contents.z += 1;
-            data.command_invoker
-                .push(AddEraser::new(*data.contents.erasers.last().unwrap()));
+            data.command_invoker.push_eraser(
+                *data.contents.erasers.last().unwrap(),
+                Duration::from_millis(data.config.eraser_merge_time.into()),
+            );
         }
     }
 
@@ -405,14 +1474,12 @@ impl StateHandler for Erasing {
         _thread: &RaylibThread,
         rl: &mut RaylibHandle,
     ) -> Transition {
-        if !rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
-            || !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT)
-        {
+        if !is_erasing(data, self.button, rl) {
             return Transition::Switch(Box::new(Idle));
         }
 
         if let Some(eraser) = self.eraser.as_mut() {
-            let d = mouse_delta(rl).to_canvas(&data.camera);
+            let d = mouse_delta(data, rl).to_canvas(&data.camera);
             eraser.rect = CanvasRect::new(
                 eraser.rect.origin,
                 CanvasSize::new(eraser.rect.size.width + d.x, eraser.rect.size.height + d.y),
@@ -425,10 +1492,123 @@ impl StateHandler for Erasing {
     }
 }
 
+impl StateHandler for ErasingStrokes {
+    fn on_enter(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        data.command_invoker.end_group();
+    }
+
+    fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        if !self.removed.is_empty() {
+            let removed = mem::take(&mut self.removed);
+            data.command_invoker.push(RemoveLines::new(removed));
+        }
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if !is_erasing(data, self.button, rl) {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        let mouse = mouse_pos(rl);
+        let pos = mouse.to_canvas(&data.camera);
+        // everything passed since the last frame counts, not just where the cursor is
+        let last = self.last.replace(pos).unwrap_or(pos);
+        let radius = data.config.eraser_radius / data.camera.zoom().0;
+        let (touched, kept): (Vec<_>, Vec<_>) = mem::take(&mut data.contents.lines)
+            .into_iter()
+            .partition(|l| l.touches(last, pos, radius));
+        data.contents.lines = kept;
+        self.removed.extend(touched);
+
+        push_eraser_cursor(data, mouse, radius);
+        Transition::Stay
+    }
+}
+
+impl ErasingPixels {
+    /// Erases a circle from every line it touches.
+    fn erase_at(&mut self, data: &mut SceneData, center: CanvasPoint, radius: f32) {
+        let mut kept = vec![];
+        for line in mem::take(&mut data.contents.lines) {
+            let Some(pieces) = line.erase(center, radius) else {
+                kept.push(line);
+                continue;
+            };
+
+            // a piece cut earlier in this drag was never there to begin with
+            if let Some(i) = self.added.iter().position(|a| a.id == line.id) {
+                self.added.swap_remove(i);
+            } else {
+                self.removed.push(line.clone());
+            }
+            for points in pieces {
+                let mut piece = line.copy_as(data.contents.next_line_id(), line.z());
+                piece.points = points;
+                self.added.push(piece.clone());
+                kept.push(piece);
+            }
+        }
+        data.contents.lines = kept;
+    }
+}
+
+impl StateHandler for ErasingPixels {
+    fn on_enter(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        data.command_invoker.end_group();
+    }
+
+    fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        if !self.removed.is_empty() {
+            let removed = mem::take(&mut self.removed);
+            let added = mem::take(&mut self.added);
+            data.command_invoker.push(SplitLines::new(removed, added));
+        }
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if !is_erasing(data, self.button, rl) {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        let mouse = mouse_pos(rl);
+        let pos = mouse.to_canvas(&data.camera);
+        let last = self.last.replace(pos).unwrap_or(pos);
+        let radius = data.config.eraser_radius / data.camera.zoom().0;
+        // erase along the way from the last frame in steps small enough to leave no gaps
+        let steps = ((pos - last).length() / (radius / 2.0)).ceil().max(1.0) as usize;
+        for i in 1..=steps {
+            self.erase_at(data, last.lerp(pos, i as f32 / steps as f32), radius);
+        }
+
+        push_eraser_cursor(data, mouse, radius);
+        Transition::Stay
+    }
+}
+
+impl ModifyingImage {
+    pub fn new(id: ImageId) -> Self {
+        Self {
+            id,
+            press: None,
+            nudged_from: None,
+        }
+    }
+}
+
 impl StateHandler for ModifyingImage {
     fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
         data.contents
-            .image(self.0)
+            .image(self.id)
             .expect("In modifying image state an image with provided id should exist")
             .is_selected = true;
 
@@ -437,11 +1617,16 @@ impl StateHandler for ModifyingImage {
     }
 
     fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
-        let Some(img) = data.contents.image(self.0) else {
+        let Some(img) = data.contents.image(self.id) else {
             return;
         };
 
         img.is_selected = false;
+        if let Some(start) = self.nudged_from {
+            let end = img.pos;
+            data.command_invoker
+                .push(command::MoveImage::new(self.id, start, end));
+        }
     }
 
     fn step(
@@ -450,39 +1635,88 @@ impl StateHandler for ModifyingImage {
         _thread: &RaylibThread,
         rl: &mut RaylibHandle,
     ) -> Transition {
-        let image_under_cursor = data.image_under_cursor(mouse_pos(rl)) != Some(self.0);
+        let mouse = mouse_pos(rl);
+        let left_image = data.image_under_cursor(mouse) != Some(self.id);
         if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
-            if image_under_cursor {
+            if left_image && self.press.is_none() {
                 return Transition::Switch(Box::new(Idle));
             }
-            return Transition::Switch(Box::new(MovingImage::new(self.0)));
+            let press = *self.press.get_or_insert(mouse);
+            if press.distance_to(mouse) > data.config.drag_threshold {
+                return Transition::Switch(Box::new(MovingImage::new(self.id, press)));
+            }
+        } else {
+            self.press = None;
         }
 
         if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
-            if image_under_cursor {
+            if left_image {
                 return Transition::Switch(Box::new(Idle));
             }
-            return Transition::Switch(Box::new(ResizingImage::new(self.0)));
+            return Transition::Switch(Box::new(ResizingImage::new(self.id)));
         }
 
         let scroll = rl.get_mouse_wheel_move_v();
-        if scroll.y != 0.0 && !rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) {
-            data.update_zoom(scroll.y);
+        if scroll.y != 0.0 && !data.config.scroll_thickness.is_down(rl) {
+            data.update_zoom(rl, scroll.y);
         }
 
         match data.input_handler.interpret(rl) {
             Action::Remove => {
-                let mut img = data.contents.remove_image(self.0).unwrap();
+                let mut img = data.contents.remove_image(self.id).unwrap();
                 img.is_selected = false;
                 data.command_invoker.push(command::RemoveImage::new(img));
 
                 return Transition::Switch(Box::new(Idle));
             }
             Action::UpLayer => {
-                data.contents.move_image_up(self.0);
+                change_layer(data, self.id, Contents::move_image_up);
             }
             Action::DownLayer => {
-                data.contents.move_image_down(self.0);
+                change_layer(data, self.id, Contents::move_image_down);
+            }
+            Action::Duplicate => {
+                // keep working on the copy so it can be dragged right away
+                if let Some(id) = duplicate_image(data, self.id) {
+                    return Transition::Switch(Box::new(ModifyingImage::new(id)));
+                }
+            }
+            Action::ExtractPalette => {
+                let img = data.contents.image(self.id).unwrap();
+                let colors = match texture::RaylibTexture2D::load_image(&*img.texture) {
+                    Ok(image) => {
+                        let pixels = image.get_image_data();
+                        // a sample is enough for big images
+                        let step = (pixels.len() / 65536).max(1);
+                        let opaque = pixels
+                            .iter()
+                            .step_by(step)
+                            .filter(|c| c.a >= 128)
+                            .copied()
+                            .collect::<Vec<_>>();
+                        palette::dominant_colors(&opaque, data.config.palette_size)
+                    }
+                    Err(e) => {
+                        eprintln!("[ERROR] Couldn't read the image ({e})");
+                        vec![]
+                    }
+                };
+
+                if colors.is_empty() {
+                    data.notify("No colors found in the image".to_string());
+                } else {
+                    data.notify(format!("Palette of {} colors", colors.len()));
+                    data.set_palette(colors);
+                }
+            }
+            Action::Nudge(x, y) => {
+                let mut step = data.config.nudge_distance;
+                if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                    step *= 10.0;
+                }
+                let img = data.contents.image(self.id).unwrap();
+                self.nudged_from.get_or_insert(img.pos);
+                img.pos += CanvasVector::new(x as f32, y as f32) * step;
             }
             Action::None => {}
             _ => {
@@ -496,11 +1730,125 @@ impl StateHandler for ModifyingImage {
     }
 }
 
+impl StateHandler for EditingNote {
+    fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        data.notes[self.0].editing = true;
+        // drop the key that started editing so it doesn't end up in the note
+        while rl.get_char_pressed().is_some() {}
+    }
+
+    fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        data.notes[self.0].editing = false;
+        if data.notes[self.0].text.is_empty() {
+            data.notes.remove(self.0);
+        }
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        match edit_text(rl, &mut data.notes[self.0].text) {
+            TextInput::Typing => Transition::Stay,
+            TextInput::Done => Transition::Switch(Box::new(Idle)),
+            TextInput::Cancel => {
+                data.notes[self.0].text.clear();
+                Transition::Switch(Box::new(Idle))
+            }
+        }
+    }
+}
+
+impl StateHandler for TypingText {
+    fn on_enter(&mut self, _data: &mut SceneData, rl: &mut RaylibHandle) {
+        // drop the key that started typing so it doesn't end up in the text
+        while rl.get_char_pressed().is_some() {}
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        match edit_text(rl, &mut self.text.text) {
+            TextInput::Typing => {}
+            // only a finished text gets added, leaving any other way throws it away
+            TextInput::Done => {
+                if !self.text.text.trim().is_empty() {
+                    data.contents.texts.push(self.text.clone());
+                    data.contents.z += 1;
+                    data.command_invoker.push(AddText::new(self.text.clone()));
+                    data.stroke_finished();
+                }
+                return Transition::Switch(Box::new(Idle));
+            }
+            TextInput::Cancel => return Transition::Switch(Box::new(Idle)),
+        }
+
+        // anchored to the canvas so it moves along with the camera
+        let x = self.text.pos.x + self.text.width().0;
+        let top = CanvasPoint::new(x, self.text.pos.y);
+        let bottom = CanvasPoint::new(x, self.text.pos.y + self.text.size.0);
+        data.contents.overlay.push(Box::new(self.text.clone()));
+        data.contents.overlay.push(Box::new(StraightLine {
+            start: top.to_screen(&data.camera),
+            end: bottom.to_screen(&data.camera),
+            brush: Brush {
+                color: self.text.color,
+                thickness: Length::new(2.0 * data.ui_scale),
+                mode: BrushMode::Pen,
+            },
+        }));
+        Transition::Stay
+    }
+}
+
+impl StateHandler for OpeningImage {
+    fn on_enter(&mut self, _data: &mut SceneData, rl: &mut RaylibHandle) {
+        // drop the key that started it so it doesn't end up in the path
+        while rl.get_char_pressed().is_some() {}
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        match edit_text(rl, &mut self.path) {
+            TextInput::Typing => {
+                data.notify(format!("Image to open: {}_", self.path));
+                Transition::Stay
+            }
+            TextInput::Done => {
+                let path = self.path.trim();
+                match data.open_image(path, SceneData::screen_center(rl), rl, thread) {
+                    Ok(()) => data.notification = None,
+                    Err(e) => {
+                        eprintln!("[ERROR] {e}");
+                        data.notify(e);
+                    }
+                }
+                Transition::Switch(Box::new(Idle))
+            }
+            TextInput::Cancel => {
+                data.notification = None;
+                Transition::Switch(Box::new(Idle))
+            }
+        }
+    }
+}
+
 impl ResizingImage {
     pub fn new(id: ImageId) -> Self {
         Self {
             id,
             start_scale: CanvasLength::new(1.0),
+            start_diagonal: CanvasVector::zero(),
+            grab: CanvasPoint::origin(),
         }
     }
 }
@@ -514,6 +1862,8 @@ impl StateHandler for ResizingImage {
             .image(self.id)
             .expect("Image id should be correct when exiting resizing state");
         self.start_scale = img.scale;
+        self.start_diagonal = CanvasVector::new(img.width().0, img.height().0);
+        self.grab = mouse_pos(rl).to_canvas(&data.camera);
     }
 
     fn on_exit(&mut self, data: &mut SceneData, _window: &mut RaylibHandle) {
@@ -536,38 +1886,55 @@ impl StateHandler for ResizingImage {
         rl: &mut RaylibHandle,
     ) -> Transition {
         if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
-            return Transition::Switch(Box::new(ModifyingImage(self.id)));
+            return Transition::Switch(Box::new(ModifyingImage::new(self.id)));
         }
 
+        // computed from where resizing started rather than the last frame
+        // so the image doesn't drift when the mouse barely moves
+        let moved = mouse_pos(rl).to_canvas(&data.camera) - self.grab;
+        let len = self.start_diagonal.length();
+        let factor = if len > 0.0 {
+            (len + moved.dot(self.start_diagonal / len)) / len
+        } else {
+            1.0
+        };
+
         let img = data
             .contents
             .image(self.id)
             .expect("Image id should be correct when in resizing state");
-        let d = mouse_delta(rl).to_canvas(&data.camera);
-        let dx = d.x / img.width().0;
-        let dy = d.y / img.height().0;
-        img.scale *= 1.0 + if dx.abs() > dy.abs() { dx } else { dy };
+        img.scale = CanvasLength::new(
+            data.config
+                .clamp_image_scale(self.start_scale.0 * factor.max(0.05)),
+        );
 
         Transition::Stay
     }
 }
 
 impl MovingImage {
-    pub fn new(id: ImageId) -> Self {
+    pub fn new(id: ImageId, grab: ScreenPoint) -> Self {
         Self {
             id,
+            grab,
             start_pos: CanvasPoint::default(),
+            raw_pos: CanvasPoint::default(),
         }
     }
 }
 
 impl StateHandler for MovingImage {
-    fn on_enter(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+    fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
         self.start_pos = data
             .contents
             .image(self.id)
             .expect("Image id should be correct when entering moving state")
             .pos;
+        // catch up with the distance moved before the drag threshold was hit
+        self.raw_pos = self.start_pos + (mouse_pos(rl) - self.grab).to_canvas(&data.camera);
+        if let Some(img) = data.contents.image(self.id) {
+            img.pos = self.raw_pos;
+        }
     }
 
     fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
@@ -593,15 +1960,598 @@ impl StateHandler for MovingImage {
         rl: &mut RaylibHandle,
     ) -> Transition {
         if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
-            return Transition::Switch(Box::new(ModifyingImage(self.id)));
+            return Transition::Switch(Box::new(ModifyingImage::new(self.id)));
         }
 
+        self.raw_pos += steady_mouse_delta(data, rl).to_canvas(&data.camera);
+        let pos = data.snap_to_grid(rl, self.raw_pos);
+
         let img = data
             .contents
             .image(self.id)
             .expect("Image id should be correct when in moving state");
-        img.pos += mouse_delta(rl).to_canvas(&data.camera);
+        img.pos = pos;
+        let bounds = img.bounds();
+
+        if data.config.smart_guides && data.snapping(rl) {
+            const TOLERANCE: f32 = 5.0;
+
+            let tolerance = TOLERANCE / data.camera.zoom().0;
+            let (offset, guides) = data.contents.align_image(self.id, bounds, tolerance);
+            if let Some(img) = data.contents.image(self.id) {
+                img.pos += offset;
+            }
+
+            let (w, h) = (rl.get_screen_width() as f32, rl.get_screen_height() as f32);
+            for guide in guides {
+                let (start, end) = match guide {
+                    Guide::Vertical(x) => {
+                        let x = CanvasPoint::new(x, 0.0).to_screen(&data.camera).x;
+                        (ScreenPoint::new(x, 0.0), ScreenPoint::new(x, h))
+                    }
+                    Guide::Horizontal(y) => {
+                        let y = CanvasPoint::new(0.0, y).to_screen(&data.camera).y;
+                        (ScreenPoint::new(0.0, y), ScreenPoint::new(w, y))
+                    }
+                };
+                data.contents.overlay.push(Box::new(StraightLine {
+                    start,
+                    end,
+                    brush: Brush {
+                        color: data.config.colors[0],
+                        thickness: Length::new(1.0),
+                        mode: BrushMode::Pen,
+                    },
+                }));
+            }
+        }
 
         Transition::Stay
     }
 }
+
+impl Lassoing {
+    pub fn new(color: Color) -> Self {
+        Self {
+            lasso: Lasso::new(color),
+        }
+    }
+}
+
+impl StateHandler for Lassoing {
+    fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        rl.show_cursor();
+        rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_CROSSHAIR);
+        data.selection.mark(&mut data.contents, false);
+        data.selection.clear();
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE)
+            || rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT)
+        {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        let mouse = mouse_pos(rl);
+        if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+            let pos = mouse.to_canvas(&data.camera);
+            let far_enough = self.lasso.points.last().is_none_or(|last| {
+                CanvasLength::new(last.distance_to(pos))
+                    .to_screen(&data.camera)
+                    .0
+                    >= 5.0
+            });
+            if far_enough {
+                self.lasso.points.push(pos);
+            }
+        } else if !self.lasso.points.is_empty() {
+            data.selection = Selection::lassoed(&self.lasso, &data.contents);
+
+            if data.selection.is_empty() {
+                return Transition::Switch(Box::new(Idle));
+            }
+            return Transition::Switch(Box::new(SelectedLines));
+        }
+
+        data.contents.overlay.push(Box::new(self.lasso.clone()));
+
+        Transition::Stay
+    }
+}
+
+impl StateHandler for Selecting {
+    fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        rl.show_cursor();
+        rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_CROSSHAIR);
+        data.selection.clear();
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE)
+            || rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT)
+        {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        let corner = mouse_pos(rl).to_canvas(&data.camera);
+        let area = CanvasBox::from_points([self.anchor, corner]);
+        if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+            data.selection = Selection::within(area, &data.contents);
+            if data.selection.is_empty() {
+                return Transition::Switch(Box::new(Idle));
+            }
+            return Transition::Switch(Box::new(SelectedLines));
+        }
+
+        data.contents.overlay.push(Box::new(Outline {
+            bounds: area,
+            color: data.config.colors[0],
+        }));
+        Transition::Stay
+    }
+}
+
+impl StateHandler for SelectedLines {
+    fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        rl.show_cursor();
+        rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_ARROW);
+        data.selection.mark(&mut data.contents, true);
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE)
+            || rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT)
+        {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        let Some(bounds) = data.selection.bounds(&data.contents) else {
+            // the selected lines are gone (e.g. undone)
+            return Transition::Switch(Box::new(Idle));
+        };
+        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            if bounds.contains(mouse_pos(rl).to_canvas(&data.camera)) {
+                return Transition::Switch(Box::new(MovingSelection::new()));
+            }
+            return Transition::Switch(Box::new(Idle));
+        }
+        data.contents.overlay.push(Box::new(Outline {
+            bounds,
+            color: data.config.colors[0],
+        }));
+
+        let scroll = rl.get_mouse_wheel_move_v();
+        if scroll.y != 0.0 && !data.config.scroll_thickness.is_down(rl) {
+            data.update_zoom(rl, scroll.y);
+        }
+
+        match data.input_handler.interpret(rl) {
+            Action::None => {}
+            Action::Lasso => {
+                return Transition::Switch(Box::new(Lassoing::new(data.config.colors[0])));
+            }
+            action @ (Action::Copy | Action::Cut) => {
+                data.copied = data.selection.copy(&data.contents);
+                // so that pasting doesn't prefer an older image
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    data.copied_over = data
+                        .clipboard
+                        .as_mut()
+                        .and_then(|c| c.get_image().ok())
+                        .map(|i| i.fingerprint());
+                }
+
+                if let Action::Cut = action {
+                    let mut cmd = RemoveSelection::new(&data.selection, &data.contents);
+                    cmd.execute(&mut data.contents);
+                    data.command_invoker.push(cmd);
+                    return Transition::Switch(Box::new(Idle));
+                }
+            }
+            Action::Paste => {
+                paste_copied(data, mouse_pos(rl));
+            }
+            Action::CopyImage => {
+                data.copy_image = Some(data.selection.clone());
+            }
+            // exports only what's selected, whichever area was asked for
+            Action::Export(_) => {
+                data.export = Some(export::ExportArea::Selection);
+            }
+            Action::ExportSvg => match data.export_svg(Some(&data.selection)) {
+                Ok(path) => data.notify(format!("Exported {}", path.display())),
+                Err(e) => {
+                    eprintln!("[ERROR] {e}");
+                    data.notify(e);
+                }
+            },
+            Action::Remove => {
+                let mut cmd = RemoveSelection::new(&data.selection, &data.contents);
+                cmd.execute(&mut data.contents);
+                data.command_invoker.push(cmd);
+                return Transition::Switch(Box::new(Idle));
+            }
+            _ => {
+                // let the Idle state handle the input on the next frame
+                return Transition::Switch(Box::new(Idle));
+            }
+        }
+
+        Transition::Stay
+    }
+}
+
+impl MovingSelection {
+    pub fn new() -> Self {
+        Self {
+            offset: CanvasVector::zero(),
+            raw_offset: CanvasVector::zero(),
+        }
+    }
+}
+
+impl StateHandler for MovingSelection {
+    fn on_enter(&mut self, _data: &mut SceneData, rl: &mut RaylibHandle) {
+        rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_RESIZE_ALL);
+    }
+
+    fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        if self.offset != CanvasVector::zero() {
+            data.command_invoker.push(command::MoveSelection::new(
+                data.selection.clone(),
+                self.offset,
+            ));
+        }
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+            return Transition::Switch(Box::new(SelectedLines));
+        }
+
+        self.raw_offset += mouse_delta(data, rl).to_canvas(&data.camera);
+        let offset = data
+            .snap_to_grid(rl, self.raw_offset.to_point())
+            .to_vector();
+        data.selection
+            .translate(&mut data.contents, offset - self.offset);
+        self.offset = offset;
+
+        if let Some(bounds) = data.selection.bounds(&data.contents) {
+            data.contents.overlay.push(Box::new(Outline {
+                bounds,
+                color: data.config.colors[0],
+            }));
+        }
+
+        Transition::Stay
+    }
+}
+
+impl ContextMenu {
+    const ITEMS: [&str; 4] = ["Remove", "Duplicate", "Bring to front", "Send to back"];
+
+    pub fn new(data: &SceneData, rl: &RaylibHandle, id: ImageId, pos: ScreenPoint) -> Self {
+        let menu = Menu::new(
+            rl,
+            pos,
+            &Self::ITEMS,
+            data.config.background,
+            data.config.colors[0],
+            data.ui_scale,
+        );
+        Self { id, menu }
+    }
+}
+
+impl StateHandler for ContextMenu {
+    fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        if let Some(img) = data.contents.image(self.id) {
+            img.is_selected = true;
+        }
+
+        rl.show_cursor();
+        rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_ARROW);
+    }
+
+    fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        if let Some(img) = data.contents.image(self.id) {
+            img.is_selected = false;
+        }
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE)
+            || rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT)
+        {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        self.menu.hovered = self.menu.item_at(mouse_pos(rl));
+        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            match self.menu.hovered {
+                Some(0) => {
+                    if let Some(mut img) = data.contents.remove_image(self.id) {
+                        img.is_selected = false;
+                        data.command_invoker.push(command::RemoveImage::new(img));
+                    }
+                }
+                Some(1) => {
+                    duplicate_image(data, self.id);
+                }
+                Some(2) => change_layer(data, self.id, Contents::move_image_to_front),
+                Some(3) => change_layer(data, self.id, Contents::move_image_to_back),
+                _ => {}
+            }
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        data.contents.overlay.push(Box::new(self.menu));
+
+        Transition::Stay
+    }
+}
+
+impl ConfirmQuit {
+    const ITEMS: [&str; 3] = ["Save and quit", "Discard changes and quit", "Cancel"];
+
+    pub fn new(data: &SceneData, rl: &RaylibHandle) -> Self {
+        Self {
+            menu: Menu::new(
+                rl,
+                SceneData::screen_center(rl),
+                &Self::ITEMS,
+                data.config.background,
+                data.config.colors[0],
+                data.ui_scale,
+            ),
+            entered: true,
+        }
+    }
+}
+
+impl StateHandler for ConfirmQuit {
+    fn on_enter(&mut self, data: &mut SceneData, rl: &mut RaylibHandle) {
+        data.confirming_quit = true;
+        rl.show_cursor();
+        rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_ARROW);
+    }
+
+    fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        data.confirming_quit = false;
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        self.menu.hovered = self.menu.item_at(mouse_pos(rl));
+        if mem::take(&mut self.entered) {
+            data.contents.overlay.push(Box::new(self.menu));
+            return Transition::Stay;
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        let choice = if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            Some(0)
+        } else if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            self.menu.hovered
+        } else {
+            None
+        };
+        match choice {
+            Some(0) => match data.save() {
+                Ok(_) => data.quit = true,
+                Err(e) => {
+                    eprintln!("[ERROR] {e}");
+                    data.notify(e);
+                    return Transition::Switch(Box::new(Idle));
+                }
+            },
+            Some(1) => data.quit = true,
+            Some(2) => return Transition::Switch(Box::new(Idle)),
+            _ => {}
+        }
+
+        data.contents.overlay.push(Box::new(self.menu));
+
+        Transition::Stay
+    }
+}
+
+impl ConfirmLoad {
+    const ITEMS: [&str; 2] = ["Discard changes and load", "Cancel"];
+
+    fn new(data: &SceneData, rl: &RaylibHandle) -> Self {
+        Self {
+            menu: Menu::new(
+                rl,
+                SceneData::screen_center(rl),
+                &Self::ITEMS,
+                data.config.background,
+                data.config.colors[0],
+                data.ui_scale,
+            ),
+        }
+    }
+}
+
+impl StateHandler for ConfirmLoad {
+    fn on_enter(&mut self, _data: &mut SceneData, rl: &mut RaylibHandle) {
+        rl.show_cursor();
+        rl.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_ARROW);
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            load_text(data, rl, thread);
+            return Transition::Switch(Box::new(Idle));
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        self.menu.hovered = self.menu.item_at(mouse_pos(rl));
+        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            match self.menu.hovered {
+                Some(0) => {
+                    load_text(data, rl, thread);
+                    return Transition::Switch(Box::new(Idle));
+                }
+                Some(1) => return Transition::Switch(Box::new(Idle)),
+                _ => {}
+            }
+        }
+
+        data.contents.overlay.push(Box::new(self.menu));
+
+        Transition::Stay
+    }
+}
+
+impl Playback {
+    // long breaks between strokes are shortened to this many seconds
+    const MAX_GAP: f32 = 1.0;
+
+    fn new(data: &SceneData) -> Self {
+        let (start, strokes) = data.timeline.replay(&data.contents);
+        let gaps = iter::once(0.0)
+            .chain(strokes.windows(2).map(|w| {
+                w[1].time
+                    .duration_since(w[0].time)
+                    .as_secs_f32()
+                    .min(Self::MAX_GAP)
+            }))
+            .take(strokes.len())
+            .collect();
+
+        Self {
+            saved: None,
+            start,
+            strokes: strokes.into_iter().map(|s| s.added).collect(),
+            gaps,
+            next: 0,
+            clock: 0.0,
+            speed: data.config.playback_speed,
+        }
+    }
+}
+
+impl StateHandler for Playback {
+    fn on_enter(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        // the tracing image stays up while the drawing is replayed on top of it
+        let mut replay = Contents::new();
+        replay.restore(mem::take(&mut self.start));
+        replay.tracing = data.contents.tracing.take();
+        replay.show_tracing = data.contents.show_tracing;
+        self.saved = Some(mem::replace(&mut data.contents, replay));
+        data.playing_back = true;
+    }
+
+    fn on_exit(&mut self, data: &mut SceneData, _rl: &mut RaylibHandle) {
+        if let Some(mut contents) = self.saved.take() {
+            contents.tracing = data.contents.tracing.take();
+            contents.show_tracing = data.contents.show_tracing;
+            data.contents = contents;
+        }
+        data.playing_back = false;
+    }
+
+    fn step(
+        &mut self,
+        data: &mut SceneData,
+        _thread: &RaylibThread,
+        rl: &mut RaylibHandle,
+    ) -> Transition {
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE)
+            || matches!(data.input_handler.interpret(rl), Action::Playback)
+        {
+            return Transition::Switch(Box::new(Idle));
+        }
+
+        if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
+            let delta = steady_mouse_delta(data, rl);
+            data.camera.update_pos(delta);
+        }
+
+        let scroll = rl.get_mouse_wheel_move_v();
+        if scroll.y != 0.0 {
+            self.speed = (self.speed * 1.5f32.powf(scroll.y.signum())).clamp(0.25, 16.0);
+            data.notify(format!("Playback speed {:.2}x", self.speed));
+        }
+
+        let playing = self.next < self.gaps.len();
+        self.clock += rl.get_frame_time() * self.speed;
+        while let Some(&gap) = self.gaps.get(self.next)
+            && self.clock >= gap
+        {
+            self.clock -= gap;
+            let stroke = mem::take(&mut self.strokes[self.next]);
+            data.contents.add(stroke);
+            self.next += 1;
+        }
+        if playing && self.next == self.gaps.len() {
+            data.notify("Playback finished".to_string());
+        }
+
+        Transition::Stay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_size_skips_empty_windows() {
+        assert_eq!(camera_size(0, 600, false), None);
+        assert_eq!(camera_size(800, 0, false), None);
+        assert_eq!(camera_size(0, 0, false), None);
+    }
+
+    #[test]
+    fn camera_size_skips_minimized_windows() {
+        assert_eq!(camera_size(800, 600, true), None);
+        assert_eq!(
+            camera_size(800, 600, false),
+            Some(ScreenSize::new(800.0, 600.0))
+        );
+    }
+}